@@ -216,6 +216,17 @@ pub const BOARD_END: usize = 10;
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Point(pub usize, pub usize);
 
+impl Point {
+    // index of this point on an 8x8 board, a8 is 0 and h1 is 63
+    pub fn square_index(self) -> usize {
+        (self.0 - BOARD_START) * 8 + self.1 - BOARD_START
+    }
+
+    pub fn from_square_index(index: usize) -> Self {
+        Point(index / 8 + BOARD_START, index % 8 + BOARD_START)
+    }
+}
+
 impl FromStr for Point {
     type Err = &'static str;
 
@@ -279,6 +290,146 @@ impl fmt::Display for Point {
     }
 }
 
+/*
+    A move packed into 16 bits, 6 bits for each square and 4 bits of flags
+    See https://www.chessprogramming.org/Encoding_Moves#From-To_Based
+
+    flags   meaning
+    0000    quiet move
+    0001    double pawn push
+    0010    king side castle
+    0011    queen side castle
+    0100    capture
+    0101    en passant capture
+    1000    knight promotion, 1001 bishop, 1010 rook, 1011 queen
+    1100    knight promotion with capture, 1101 bishop, 1110 rook, 1111 queen
+*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Move(u16);
+
+const DOUBLE_PAWN_PUSH_FLAG: u16 = 0b0001;
+const KING_CASTLE_FLAG: u16 = 0b0010;
+const QUEEN_CASTLE_FLAG: u16 = 0b0011;
+const CAPTURE_FLAG: u16 = 0b0100;
+const EN_PASSANT_FLAG: u16 = 0b0101;
+const PROMOTION_FLAG: u16 = 0b1000;
+
+impl Move {
+    fn new(start: Point, end: Point, flags: u16) -> Self {
+        Move(flags << 12 | (start.square_index() as u16) << 6 | end.square_index() as u16)
+    }
+
+    pub fn quiet(start: Point, end: Point) -> Self {
+        Move::new(start, end, 0)
+    }
+
+    pub fn capture(start: Point, end: Point) -> Self {
+        Move::new(start, end, CAPTURE_FLAG)
+    }
+
+    pub fn double_pawn_push(start: Point, end: Point) -> Self {
+        Move::new(start, end, DOUBLE_PAWN_PUSH_FLAG)
+    }
+
+    pub fn en_passant(start: Point, end: Point) -> Self {
+        Move::new(start, end, EN_PASSANT_FLAG)
+    }
+
+    // castling is encoded as the king's move, the direction decides which side
+    pub fn castle(start: Point, end: Point) -> Self {
+        if end.1 > start.1 {
+            Move::new(start, end, KING_CASTLE_FLAG)
+        } else {
+            Move::new(start, end, QUEEN_CASTLE_FLAG)
+        }
+    }
+
+    pub fn promotion(start: Point, end: Point, kind: PieceKind, capture: bool) -> Self {
+        let piece_flag = match kind {
+            Knight => 0,
+            Bishop => 1,
+            Rook => 2,
+            _ => 3,
+        };
+        let capture_flag = if capture { CAPTURE_FLAG } else { 0 };
+        Move::new(start, end, PROMOTION_FLAG | capture_flag | piece_flag)
+    }
+
+    // raw representation, used to store moves compactly
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        Move(bits)
+    }
+
+    pub fn start(self) -> Point {
+        Point::from_square_index((self.0 >> 6 & 0b111111) as usize)
+    }
+
+    pub fn end(self) -> Point {
+        Point::from_square_index((self.0 & 0b111111) as usize)
+    }
+
+    fn flags(self) -> u16 {
+        self.0 >> 12
+    }
+
+    pub fn is_capture(self) -> bool {
+        self.flags() & CAPTURE_FLAG != 0
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.flags() == EN_PASSANT_FLAG
+    }
+
+    pub fn is_double_pawn_push(self) -> bool {
+        self.flags() == DOUBLE_PAWN_PUSH_FLAG
+    }
+
+    pub fn is_castle(self) -> bool {
+        self.flags() == KING_CASTLE_FLAG || self.flags() == QUEEN_CASTLE_FLAG
+    }
+
+    pub fn promotion_kind(self) -> Option<PieceKind> {
+        if self.flags() & PROMOTION_FLAG == 0 {
+            return None;
+        }
+        match self.flags() & 0b11 {
+            0 => Some(Knight),
+            1 => Some(Bishop),
+            2 => Some(Rook),
+            _ => Some(Queen),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    // Long algebraic notation as used by UCI, ex e2e4 or e7e8q
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.promotion_kind() {
+            Some(kind) => write!(f, "{}{}{}", self.start(), self.end(), kind.alg()),
+            None => write!(f, "{}{}", self.start(), self.end()),
+        }
+    }
+}
+
+/*
+    Everything make_move can not recover by itself when taking back a move
+*/
+#[derive(Copy, Clone)]
+pub struct UndoState {
+    captured: Square,
+    pawn_double_move: Option<Point>,
+    white_king_side_castle: bool,
+    white_queen_side_castle: bool,
+    black_king_side_castle: bool,
+    black_queen_side_castle: bool,
+    zobrist_key: u64,
+    half_move_clock: u8,
+}
+
 #[derive(Clone)]
 pub struct BoardState {
     pub board: [[Square; 12]; 12],
@@ -290,15 +441,14 @@ pub struct BoardState {
     pub white_queen_side_castle: bool,
     pub black_king_side_castle: bool,
     pub black_queen_side_castle: bool,
-    pub order_heuristic: i32, // value set to help order this board, a higher value means this board state will be considered first
-    pub last_move: Option<(Point, Point)>, // the start and last position of the last move made
-    pub pawn_promotion: Option<Piece>, // set to the chosen pawn promotion type
     pub zobrist_key: u64,
+    pub half_move_clock: u8, // number of half moves since the last capture or pawn move
+    pub full_move_number: u16, // starts at 1 and is incremented after black's move
 }
 
 impl BoardState {
     // Parse the standard fen string notation (en.wikipedia.org/wiki/Forsyth–Edwards_Notation) and return a board state
-    pub fn from_fen(fen: &str) -> Result<BoardState, &str> {
+    pub fn from_fen(fen: &str) -> Result<BoardState, &'static str> {
         let mut board = [[Square::Boundary; 12]; 12];
         let mut fen = fen.to_string();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
@@ -322,15 +472,15 @@ impl BoardState {
         let castling_privileges = fen_config[2];
         let en_passant = fen_config[3];
 
-        let half_move_clock = fen_config[4].parse::<u8>();
-        if half_move_clock.is_err() {
-            return Err("Could not parse fen string: Invalid half move value");
-        }
+        let half_move_clock = match fen_config[4].parse::<u8>() {
+            Ok(clock) => clock,
+            Err(_) => return Err("Could not parse fen string: Invalid half move value"),
+        };
 
-        let full_move_clock = fen_config[5].parse::<u8>();
-        if full_move_clock.is_err() {
-            return Err("Could not parse fen string: Invalid full move value");
-        }
+        let full_move_number = match fen_config[5].parse::<u16>() {
+            Ok(number) => number,
+            Err(_) => return Err("Could not parse fen string: Invalid full move value"),
+        };
 
         let fen_rows: Vec<&str> = fen_config[0].split('/').collect();
 
@@ -342,6 +492,7 @@ impl BoardState {
         let mut col: usize = BOARD_START;
         let mut white_king_location = Point(0, 0);
         let mut black_king_location = Point(0, 0);
+        #[allow(clippy::explicit_counter_loop)]
         for fen_row in fen_rows {
             for square in fen_row.chars() {
                 if row >= BOARD_END || col >= BOARD_END {
@@ -406,10 +557,9 @@ impl BoardState {
             white_queen_side_castle: castling_privileges.find('Q').is_some(),
             black_king_side_castle: castling_privileges.find('k').is_some(),
             black_queen_side_castle: castling_privileges.find('q').is_some(),
-            order_heuristic: 0,
-            last_move: None,
-            pawn_promotion: None,
             zobrist_key,
+            half_move_clock,
+            full_move_number,
         };
 
         if board.white_king_side_castle {
@@ -428,6 +578,68 @@ impl BoardState {
         Ok(board)
     }
 
+    // Write the board state out as a fen string, the inverse of from_fen
+    pub fn to_fen(&self) -> String {
+        let mut rows = Vec::with_capacity(8);
+        for i in BOARD_START..BOARD_END {
+            let mut row = String::new();
+            let mut empty_squares = 0;
+            for j in BOARD_START..BOARD_END {
+                match self.board[i][j] {
+                    Square::Full(piece) => {
+                        if empty_squares > 0 {
+                            row += &empty_squares.to_string();
+                            empty_squares = 0;
+                        }
+                        row += piece.simple_char();
+                    }
+                    _ => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                row += &empty_squares.to_string();
+            }
+            rows.push(row);
+        }
+
+        let to_move = match self.to_move {
+            White => "w",
+            Black => "b",
+        };
+
+        let mut castling_privileges = String::new();
+        if self.white_king_side_castle {
+            castling_privileges.push('K');
+        }
+        if self.white_queen_side_castle {
+            castling_privileges.push('Q');
+        }
+        if self.black_king_side_castle {
+            castling_privileges.push('k');
+        }
+        if self.black_queen_side_castle {
+            castling_privileges.push('q');
+        }
+        if castling_privileges.is_empty() {
+            castling_privileges.push('-');
+        }
+
+        let en_passant = match self.pawn_double_move {
+            Some(point) => point.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            rows.join("/"),
+            to_move,
+            castling_privileges,
+            en_passant,
+            self.half_move_clock,
+            self.full_move_number
+        )
+    }
+
     fn piece_from_fen_string_char(piece: char) -> Option<Piece> {
         match piece {
             'r' => Some(Piece {
@@ -582,6 +794,179 @@ impl BoardState {
                 ^ zobrist_hasher.get_val_for_piece(cur_piece, end);
         }
     }
+
+    /*
+        Play a move on the board, the move is assumed to be legal
+
+        Returns the state required to take the move back with unmake_move
+    */
+    pub fn make_move(&mut self, mov: Move, zobrist_hasher: &ZobristHasher) -> UndoState {
+        let start = mov.start();
+        let end = mov.end();
+        let color = self.to_move;
+        let undo = UndoState {
+            captured: self.board[end.0][end.1],
+            pawn_double_move: self.pawn_double_move,
+            white_king_side_castle: self.white_king_side_castle,
+            white_queen_side_castle: self.white_queen_side_castle,
+            black_king_side_castle: self.black_king_side_castle,
+            black_queen_side_castle: self.black_queen_side_castle,
+            zobrist_key: self.zobrist_key,
+            half_move_clock: self.half_move_clock,
+        };
+
+        // the fifty move rule counter is reset by any capture or pawn move
+        if mov.is_capture() || self.board[start.0][start.1] == Piece::pawn(color) {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock = self.half_move_clock.saturating_add(1);
+        }
+        if color == Black {
+            self.full_move_number += 1;
+        }
+
+        self.unset_pawn_double_move(zobrist_hasher);
+        self.move_piece(start, end, zobrist_hasher);
+
+        if mov.is_castle() {
+            // the king has already been moved, now move the rook
+            if end.1 > start.1 {
+                self.move_piece(
+                    Point(start.0, BOARD_END - 1),
+                    Point(start.0, BOARD_END - 3),
+                    zobrist_hasher,
+                );
+            } else {
+                self.move_piece(
+                    Point(start.0, BOARD_START),
+                    Point(start.0, BOARD_START + 3),
+                    zobrist_hasher,
+                );
+            }
+        } else if mov.is_en_passant() {
+            // the captured pawn is beside the starting square, not on the target square
+            self.board[start.0][end.1] = Square::Empty;
+            self.zobrist_key ^= zobrist_hasher
+                .get_val_for_piece(Piece::pawn(color.opposite()), Point(start.0, end.1));
+        } else if mov.is_double_pawn_push() {
+            // record the space *behind* the pawn ie the valid capture square
+            let en_passant_square = Point((start.0 + end.0) / 2, start.1);
+            self.pawn_double_move = Some(en_passant_square);
+            self.zobrist_key ^= zobrist_hasher.get_val_for_en_passant(en_passant_square.1);
+        } else if let Some(kind) = mov.promotion_kind() {
+            let promotion_piece = Piece { color, kind };
+            self.board[end.0][end.1] = Square::Full(promotion_piece);
+            self.zobrist_key ^= zobrist_hasher.get_val_for_piece(Piece::pawn(color), end)
+                ^ zobrist_hasher.get_val_for_piece(promotion_piece, end);
+        }
+
+        if self.board[end.0][end.1] == Piece::king(color) {
+            match color {
+                White => {
+                    self.white_king_location = end;
+                    self.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
+                    self.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
+                }
+                Black => {
+                    self.black_king_location = end;
+                    self.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
+                    self.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
+                }
+            }
+        }
+
+        // moving a rook or capturing a rook removes the castling privileges for that rook
+        for point in [start, end] {
+            if point == Point(BOARD_END - 1, BOARD_END - 1) {
+                self.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
+            } else if point == Point(BOARD_END - 1, BOARD_START) {
+                self.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
+            } else if point == Point(BOARD_START, BOARD_START) {
+                self.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
+            } else if point == Point(BOARD_START, BOARD_END - 1) {
+                self.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
+            }
+        }
+
+        self.swap_color(zobrist_hasher);
+        undo
+    }
+
+    /*
+        Take back a move made with make_move, the board will be identical to
+        the board before the move was made
+    */
+    pub fn unmake_move(&mut self, mov: Move, undo: &UndoState) {
+        let start = mov.start();
+        let end = mov.end();
+        let color = self.to_move.opposite();
+        self.to_move = color;
+
+        let moved_piece = if mov.promotion_kind().is_some() {
+            Square::Full(Piece::pawn(color))
+        } else {
+            self.board[end.0][end.1]
+        };
+        self.board[start.0][start.1] = moved_piece;
+        self.board[end.0][end.1] = undo.captured;
+
+        if mov.is_castle() {
+            if end.1 > start.1 {
+                self.board[start.0][BOARD_END - 3] = Square::Empty;
+                self.board[start.0][BOARD_END - 1] = Square::Full(Piece::rook(color));
+            } else {
+                self.board[start.0][BOARD_START + 3] = Square::Empty;
+                self.board[start.0][BOARD_START] = Square::Full(Piece::rook(color));
+            }
+        } else if mov.is_en_passant() {
+            self.board[start.0][end.1] = Square::Full(Piece::pawn(color.opposite()));
+        }
+
+        if moved_piece == Piece::king(color) {
+            match color {
+                White => self.white_king_location = start,
+                Black => self.black_king_location = start,
+            }
+        }
+
+        self.pawn_double_move = undo.pawn_double_move;
+        self.white_king_side_castle = undo.white_king_side_castle;
+        self.white_queen_side_castle = undo.white_queen_side_castle;
+        self.black_king_side_castle = undo.black_king_side_castle;
+        self.black_queen_side_castle = undo.black_queen_side_castle;
+        self.zobrist_key = undo.zobrist_key;
+        self.half_move_clock = undo.half_move_clock;
+        if color == Black {
+            self.full_move_number -= 1;
+        }
+    }
+
+    /*
+        Pass the turn to the other player, used for null move pruning
+    */
+    pub fn make_null_move(&mut self, zobrist_hasher: &ZobristHasher) -> UndoState {
+        let undo = UndoState {
+            captured: Square::Empty,
+            pawn_double_move: self.pawn_double_move,
+            white_king_side_castle: self.white_king_side_castle,
+            white_queen_side_castle: self.white_queen_side_castle,
+            black_king_side_castle: self.black_king_side_castle,
+            black_queen_side_castle: self.black_queen_side_castle,
+            zobrist_key: self.zobrist_key,
+            half_move_clock: self.half_move_clock,
+        };
+        self.half_move_clock = self.half_move_clock.saturating_add(1);
+        self.unset_pawn_double_move(zobrist_hasher);
+        self.swap_color(zobrist_hasher);
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: &UndoState) {
+        self.to_move = self.to_move.opposite();
+        self.pawn_double_move = undo.pawn_double_move;
+        self.zobrist_key = undo.zobrist_key;
+        self.half_move_clock = undo.half_move_clock;
+    }
 }
 
 #[cfg(test)]
@@ -652,6 +1037,150 @@ mod tests {
         assert_eq!(res, "e6");
     }
 
+    // Move tests
+
+    #[test]
+    fn move_encoding() {
+        let mov = Move::quiet(Point(9, 3), Point(7, 4));
+        assert_eq!(mov.start(), Point(9, 3));
+        assert_eq!(mov.end(), Point(7, 4));
+        assert!(!mov.is_capture());
+        assert_eq!(mov.to_string(), "b1c3");
+
+        let mov = Move::promotion(Point(3, 9), Point(2, 8), Rook, true);
+        assert!(mov.is_capture());
+        assert_eq!(mov.promotion_kind(), Some(Rook));
+        assert_eq!(mov.to_string(), "h7g8r");
+        assert_eq!(Move::from_bits(mov.to_bits()), mov);
+
+        let mov = Move::en_passant(Point(5, 6), Point(4, 7));
+        assert!(mov.is_capture());
+        assert!(mov.is_en_passant());
+        assert_eq!(mov.promotion_kind(), None);
+
+        assert!(Move::castle(Point(9, 6), Point(9, 4)).is_castle());
+        assert!(Move::double_pawn_push(Point(8, 2), Point(6, 2)).is_double_pawn_push());
+    }
+
+    fn assert_same_board(a: &BoardState, b: &BoardState) {
+        for i in BOARD_START..BOARD_END {
+            for j in BOARD_START..BOARD_END {
+                assert_eq!(a.board[i][j], b.board[i][j]);
+            }
+        }
+        assert_eq!(a.to_move, b.to_move);
+        assert_eq!(a.pawn_double_move, b.pawn_double_move);
+        assert_eq!(a.white_king_location, b.white_king_location);
+        assert_eq!(a.black_king_location, b.black_king_location);
+        assert_eq!(a.white_king_side_castle, b.white_king_side_castle);
+        assert_eq!(a.white_queen_side_castle, b.white_queen_side_castle);
+        assert_eq!(a.black_king_side_castle, b.black_king_side_castle);
+        assert_eq!(a.black_queen_side_castle, b.black_queen_side_castle);
+        assert_eq!(a.zobrist_key, b.zobrist_key);
+        assert_eq!(a.half_move_clock, b.half_move_clock);
+        assert_eq!(a.full_move_number, b.full_move_number);
+    }
+
+    #[test]
+    fn make_unmake_restores_board() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let mut b = BoardState::from_fen(fen).unwrap();
+            let original = b.clone();
+            for mov in generate_moves(&b, MoveGenerationMode::AllMoves) {
+                let undo = b.make_move(mov, &zobrist_hasher);
+                b.unmake_move(mov, &undo);
+                assert_same_board(&b, &original);
+            }
+        }
+    }
+
+    #[test]
+    fn make_move_matches_fen() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut b = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        b.make_move(
+            Move::double_pawn_push("e2".parse().unwrap(), "e4".parse().unwrap()),
+            &zobrist_hasher,
+        );
+        let expected =
+            BoardState::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                .unwrap();
+        assert_same_board(&b, &expected);
+
+        // castling king side removes both castling rights and moves the rook
+        let mut b = BoardState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        b.make_move(
+            Move::castle("e8".parse().unwrap(), "g8".parse().unwrap()),
+            &zobrist_hasher,
+        );
+        let expected = BoardState::from_fen("r4rk1/8/8/8/8/8/8/R3K2R w KQ - 1 2").unwrap();
+        assert_same_board(&b, &expected);
+
+        // capturing a rook removes that rook's castling rights
+        let mut b = BoardState::from_fen("r3k2r/8/8/8/8/8/6p1/R3K2R b KQkq - 0 1").unwrap();
+        b.make_move(
+            Move::promotion("g2".parse().unwrap(), "h1".parse().unwrap(), Queen, true),
+            &zobrist_hasher,
+        );
+        let expected = BoardState::from_fen("r3k2r/8/8/8/8/8/8/R3K2q w Qkq - 0 2").unwrap();
+        assert_same_board(&b, &expected);
+    }
+
+    #[test]
+    fn move_counters_updated() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut b = BoardState::from_fen("r3k2r/8/8/8/3p4/8/4P3/R3K1NR w KQkq - 12 40").unwrap();
+
+        // a quiet piece move ticks the clock, black moving starts the next full move
+        b.make_move(
+            Move::quiet("g1".parse().unwrap(), "f3".parse().unwrap()),
+            &zobrist_hasher,
+        );
+        assert_eq!((b.half_move_clock, b.full_move_number), (13, 40));
+        b.make_move(
+            Move::quiet("a8".parse().unwrap(), "a7".parse().unwrap()),
+            &zobrist_hasher,
+        );
+        assert_eq!((b.half_move_clock, b.full_move_number), (14, 41));
+
+        // pawn moves and captures reset the clock
+        let before_push = b.clone();
+        let push = Move::double_pawn_push("e2".parse().unwrap(), "e4".parse().unwrap());
+        let undo = b.make_move(push, &zobrist_hasher);
+        assert_eq!((b.half_move_clock, b.full_move_number), (0, 41));
+        b.make_move(
+            Move::en_passant("d4".parse().unwrap(), "e3".parse().unwrap()),
+            &zobrist_hasher,
+        );
+        assert_eq!((b.half_move_clock, b.full_move_number), (0, 42));
+
+        // and taking a move back restores the counters
+        let mut b = before_push.clone();
+        b.make_move(push, &zobrist_hasher);
+        b.unmake_move(push, &undo);
+        assert_same_board(&b, &before_push);
+    }
+
+    #[test]
+    fn null_move_restores_board() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut b =
+            BoardState::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                .unwrap();
+        let original = b.clone();
+        let undo = b.make_null_move(&zobrist_hasher);
+        assert_eq!(b.to_move, White);
+        assert_eq!(b.pawn_double_move, None);
+        assert_ne!(b.zobrist_key, original.zobrist_key);
+        b.unmake_null_move(&undo);
+        assert_same_board(&b, &original);
+    }
+
     // Zobrist hashing tests
 
     #[test]
@@ -943,6 +1472,14 @@ mod tests {
         assert_eq!(b.board[9][8], Square::from(Piece::bishop(Black)));
     }
 
+    #[test]
+    fn correct_move_counters() {
+        let b = BoardState::from_fen("8/8/8/8/3K4/8/8/k7 b - - 99 300").unwrap();
+        assert_eq!(b.half_move_clock, 99);
+        assert_eq!(b.full_move_number, 300);
+        assert!(BoardState::from_fen("8/8/8/8/3K4/8/8/k7 b - - -1 30").is_err());
+    }
+
     #[test]
     fn bad_fen_string() {
         assert!(BoardState::from_fen("this isn't a fen string").is_err());
@@ -963,4 +1500,49 @@ mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn to_fen_matches_input() {
+        for fen in [
+            DEFAULT_FEN_STRING,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "8/8/8/8/3K4/8/8/k7 b - - 37 81",
+        ] {
+            assert_eq!(BoardState::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    // Check that every position reachable within depth plies survives a trip through to_fen
+    fn assert_fen_round_trip(b: &mut BoardState, depth: usize, zobrist_hasher: &ZobristHasher) {
+        let fen = b.to_fen();
+        assert_same_board(b, &BoardState::from_fen(&fen).unwrap());
+        if depth == 0 {
+            return;
+        }
+        for mov in generate_moves(b, MoveGenerationMode::AllMoves) {
+            let undo = b.make_move(mov, zobrist_hasher);
+            assert_fen_round_trip(b, depth - 1, zobrist_hasher);
+            b.unmake_move(mov, &undo);
+        }
+    }
+
+    #[test]
+    fn fen_round_trip_perft_positions() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        // the positions used by the perft tests in move_generation
+        for fen in [
+            DEFAULT_FEN_STRING,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ] {
+            let mut b = BoardState::from_fen(fen).unwrap();
+            assert_fen_round_trip(&mut b, 2, &zobrist_hasher);
+        }
+    }
 }
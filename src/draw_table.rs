@@ -1,6 +1,9 @@
 use crate::{board::BoardState, zobrist::ZobristKey};
 use std::collections::HashMap;
 
+// a game is drawn once 50 moves have been played by each side without a capture or pawn move
+pub const FIFTY_MOVE_RULE_PLIES: u8 = 100;
+
 #[derive(Clone)]
 pub struct DrawTable {
     pub table: HashMap<ZobristKey, u8>,
@@ -44,6 +47,14 @@ impl DrawTable {
     }
 }
 
+/*
+    Check if the position is drawn by the fifty move rule, a checkmate delivered on
+    the final move still counts so the caller must rule that out if it matters
+*/
+pub fn is_fifty_move_draw(board: &BoardState) -> bool {
+    board.half_move_clock >= FIFTY_MOVE_RULE_PLIES
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,4 +93,12 @@ mod tests {
         draw_table.add_board_to_draw_table(&board);
         assert_eq!(*draw_table.table.get(&board.zobrist_key).unwrap(), 3);
     }
+
+    #[test]
+    fn draw_detected_fifty_moves() {
+        let board = BoardState::from_fen("8/8/8/8/3K4/8/8/k6R w - - 99 80").unwrap();
+        assert!(!is_fifty_move_draw(&board));
+        let board = BoardState::from_fen("8/8/8/8/3K4/8/8/k6R w - - 100 80").unwrap();
+        assert!(is_fifty_move_draw(&board));
+    }
 }
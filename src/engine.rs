@@ -1,39 +1,94 @@
 pub use crate::board::*;
 pub use crate::board::{PieceColor::*, PieceKind::*};
-use crate::draw_table::DrawTable;
+use crate::draw_table::{is_fifty_move_draw, DrawTable};
 pub use crate::evaluation::*;
+use crate::history::{History, MAX_HISTORY};
+use crate::mate_solver::{MateResult, MateSolver};
+use crate::material::MaterialSignature;
 pub use crate::move_generation::*;
-pub use crate::search::{Search, KILLER_MOVE_PLY_SIZE, MAX_DEPTH};
+use crate::notation::move_to_san;
+use crate::pgn::{eval_comment, GameResult, PgnGame};
+pub use crate::search::{
+    MoveArray, Search, SearchLimits, SharedSearch, DEFAULT_THREADS, MAX_DEPTH,
+};
+use crate::transposition_table::{Bound, TranspositionTable, DEFAULT_HASH_SIZE_MB};
 pub use crate::uci::send_to_gui;
-pub use crate::utils::out_of_time;
 use crate::zobrist::ZobristHasher;
 use std::cmp::{max, min, Reverse};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
-const MATE_SCORE: i32 = 100000;
+pub const MATE_SCORE: i32 = 100000;
+// any score past this is a mate found within the search
+const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_DEPTH as i32;
 const POS_INF: i32 = 9999999;
 const NEG_INF: i32 = -POS_INF;
+// aspiration windows start this many centipawns either side of the last score
+const ASPIRATION_WINDOW: i32 = 60;
+const ASPIRATION_MIN_DEPTH: u8 = 4;
+// a hash move is singular when every other move scores this much per ply below it
+const SINGULAR_MARGIN: i32 = 2;
+const SINGULAR_MIN_DEPTH: u8 = 8;
+// null move cutoffs from this depth on are checked by a search without the null move
+const NULL_MOVE_VERIFICATION_DEPTH: u8 = 10;
 /*
-    We want killer moves to be ordered behind all "good" captures, but still ahead of other moves
-    For our purposes a good capture is capturing any with a piece of lower value
+    Captures are split by their static exchange evaluation, a good capture wins material
+    or trades evenly once every recapture on the square has been played out
 
-    Ex: capturing a pawn with a queen is a "bad" capture
-        capturing a queen with a pawn is a "good" capture
+    Ex: capturing a defended pawn with a queen is a "bad" capture
+        capturing a defended queen with a pawn is a "good" capture
 
-    For this reason we give killer moves a 25, or ranked slightly between both types of captures
+    Good captures and queen promotions come before killer moves, bad captures are only
+    tried after every quiet move, under promotions still come last
 */
+const GOOD_CAPTURE_OFFSET: i32 = 1000;
 const KILLER_MOVE_SCORE: i32 = 25;
+// the move stored in the transposition table is tried right after the principle variation
+const HASH_MOVE_SCORE: i32 = POS_INF - 1;
+/*
+    Quiet moves come after every capture, the move that refuted the previous move first
+    and then the rest ranked by their history, which is shifted so it stays below zero
+*/
+const COUNTER_MOVE_SCORE: i32 = 0;
+const QUIET_MOVE_OFFSET: i32 = -3 * MAX_HISTORY - 1;
+const BAD_CAPTURE_OFFSET: i32 = QUIET_MOVE_OFFSET - 3 * MAX_HISTORY - 100;
+
+// rank a capture or promotion by whether it loses material, then by MVV-LVA
+fn capture_score(board: &BoardState, mov: Move) -> i32 {
+    let score = order_heuristic(board, mov);
+    if mov.promotion_kind().is_some_and(|kind| kind != Queen) {
+        score
+    } else if board.see(mov) >= 0 {
+        GOOD_CAPTURE_OFFSET + score
+    } else {
+        BAD_CAPTURE_OFFSET + score
+    }
+}
+
+/*
+    The best move found so far, along with the score and depth of the search that found it
+    A depth of 0 means the search ran out of time before completing a single move
+
+    The ponder move is the reply the principle variation expects, which can be searched
+    while waiting for the opponent to move
+*/
+#[derive(Copy, Clone)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub ponder_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+}
 
-type BoardSender = std::sync::mpsc::Sender<BoardState>;
+type MoveSender = std::sync::mpsc::Sender<SearchResult>;
 
 /*
     Capture extension, only search captures from here on to
     find a "quite" position
 */
 fn quiesce(
-    board: &BoardState,
+    board: &mut BoardState,
     mut alpha: i32,
     beta: i32,
     search_info: &mut Search,
@@ -48,10 +103,16 @@ fn quiesce(
         alpha = stand_pat;
     }
 
-    let mut moves = generate_moves(board, MoveGenerationMode::CapturesOnly, zobrist_hasher);
-    moves.sort_unstable_by_key(|k| Reverse(k.order_heuristic));
+    let mut moves = generate_moves(board, MoveGenerationMode::CapturesOnly);
+    moves.sort_by_cached_key(|&mov| Reverse(order_heuristic(board, mov)));
     for mov in moves {
-        let score = -quiesce(&mov, -beta, -alpha, search_info, zobrist_hasher);
+        // a capture that loses material won't raise alpha once the opponent takes back
+        if board.see(mov) < 0 {
+            continue;
+        }
+        let undo = board.make_move(mov, zobrist_hasher);
+        let score = -quiesce(board, -beta, -alpha, search_info, zobrist_hasher);
+        board.unmake_move(mov, &undo);
         if score >= beta {
             return beta;
         }
@@ -70,7 +131,7 @@ fn quiesce(
 fn alpha_beta_search(
     start: Instant,
     time_to_move_ms: u128,
-    board: &BoardState,
+    board: &mut BoardState,
     mut depth: u8,
     ply_from_root: i32,
     mut alpha: i32,
@@ -79,12 +140,20 @@ fn alpha_beta_search(
     allow_null: bool,
     zobrist_hasher: &ZobristHasher,
     draw_table: &mut DrawTable,
+    transposition_table: &TranspositionTable,
 ) -> i32 {
-    // we are out of time, exit the search
-    if out_of_time(start, time_to_move_ms) {
+    search_info.clear_pv(ply_from_root);
+
+    // we are out of time or nodes, exit the search
+    if search_info.should_stop(start, time_to_move_ms) {
         return NEG_INF;
     }
 
+    // check extensions could otherwise take the search past the end of the ply tables
+    if ply_from_root >= MAX_DEPTH as i32 - 1 {
+        return get_evaluation(board);
+    }
+
     search_info.node_searched();
 
     // check for draw
@@ -92,6 +161,20 @@ fn alpha_beta_search(
         return 0;
     }
 
+    // neither side can win, no point searching any further
+    let material = MaterialSignature::from_board(board);
+    if ply_from_root > 0 && material.is_insufficient_material() {
+        return 0;
+    }
+
+    // checkmate takes precedence over the fifty move rule, so make sure there is a legal reply
+    if is_fifty_move_draw(board)
+        && (!is_check(board, board.to_move)
+            || !generate_moves(board, MoveGenerationMode::AllMoves).is_empty())
+    {
+        return 0;
+    }
+
     draw_table.add_board_to_draw_table(board);
 
     if depth == 0 {
@@ -113,36 +196,110 @@ fn alpha_beta_search(
         return alpha;
     }
 
+    // Check if we have already searched this position deep enough to reuse the result
+    // Only cut off in zero window nodes so the principle variation stays intact
+    let tt_entry = transposition_table.probe(board.zobrist_key, ply_from_root);
+    if let Some(entry) = tt_entry {
+        if beta - alpha == 1
+            && entry.depth >= depth
+            && match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            }
+        {
+            draw_table.remove_board_from_draw_table(board);
+            return entry.score;
+        }
+    }
+
+    let in_check = is_check(board, board.to_move);
+    let is_pv_node = beta - alpha > 1;
+    // Forward pruning trusts the static evaluation, which means nothing in check or
+    // once a mate has been found, and is kept out of the principle variation
+    let static_eval =
+        (!is_pv_node && !in_check && alpha.abs() < MATE_THRESHOLD && beta.abs() < MATE_THRESHOLD)
+            .then(|| get_evaluation(board));
+
+    if let Some(eval) = static_eval {
+        // Reverse futility pruning, we are so far ahead that the opponent can't catch up
+        if let Some(margin) = search_info.params.reverse_futility_margin(depth) {
+            if eval - margin >= beta {
+                draw_table.remove_board_from_draw_table(board);
+                return beta;
+            }
+        }
+
+        // Razoring, we are so far behind that only captures could help
+        if let Some(margin) = search_info.params.razor_margin(depth) {
+            if eval + margin < alpha {
+                let score = quiesce(board, alpha - 1, alpha, search_info, zobrist_hasher);
+                if score < alpha {
+                    draw_table.remove_board_from_draw_table(board);
+                    return score;
+                }
+            }
+        }
+    }
+
     // Null move pruning https://www.chessprogramming.org/Null_Move_Pruning
-    // With R = 2
-    if allow_null && depth >= 3 && !is_check(board, board.to_move) {
+    // Passing is only safe when there are pieces to move, with just the king and pawns
+    // left zugzwang is common and the null move would hide it
+    if allow_null && depth >= 3 && !in_check && material.has_non_pawn_material(board.to_move) {
+        let reduction = null_move_reduction(depth);
         // allow this player to go again
-        let mut b = board.clone();
-        b.to_move = board.to_move.opposite();
+        search_info.history.push_null_move(ply_from_root);
+        search_info.extend_line(ply_from_root, depth, 0);
+        let undo = board.make_null_move(zobrist_hasher);
         let eval = -alpha_beta_search(
             start,
             time_to_move_ms,
-            &b,
-            depth - 3,
-            ply_from_root + 10, //hack for now but passing in a large ply ensures we don't overwrite the pv
+            board,
+            depth - 1 - reduction,
+            ply_from_root + 1,
             -beta,
             -beta + 1,
             search_info,
             false,
             zobrist_hasher,
             draw_table,
+            transposition_table,
         );
+        board.unmake_null_move(&undo);
 
         if eval >= beta {
-            // null move prune
-            draw_table.remove_board_from_draw_table(board);
-            return beta;
+            // Deep cutoffs are verified by a reduced search of this position without a
+            // null move, so a zugzwang can't prune away a large part of the tree
+            let verified = depth < NULL_MOVE_VERIFICATION_DEPTH || {
+                draw_table.remove_board_from_draw_table(board);
+                let score = alpha_beta_search(
+                    start,
+                    time_to_move_ms,
+                    board,
+                    depth - reduction,
+                    ply_from_root,
+                    beta - 1,
+                    beta,
+                    search_info,
+                    false,
+                    zobrist_hasher,
+                    draw_table,
+                    transposition_table,
+                );
+                draw_table.add_board_to_draw_table(board);
+                score >= beta
+            };
+            if verified {
+                // null move prune
+                draw_table.remove_board_from_draw_table(board);
+                return beta;
+            }
         }
     }
 
-    let mut moves = generate_moves(board, MoveGenerationMode::AllMoves, zobrist_hasher);
+    let mut moves = generate_moves(board, MoveGenerationMode::AllMoves);
     if moves.is_empty() {
-        if is_check(board, board.to_move) {
+        if in_check {
             // checkmate
             draw_table.remove_board_from_draw_table(board);
             let mate_score = MATE_SCORE - ply_from_root;
@@ -153,79 +310,109 @@ fn alpha_beta_search(
         return 0;
     }
 
-    // rank killer moves and pv moves
-    for mov in &mut moves {
-        if mov.last_move == search_info.pv_moves[ply_from_root as usize] {
+    // rank killer moves, hash moves, pv moves and quiet moves by their history
+    let pv_move = search_info.pv_moves[ply_from_root as usize];
+    let hash_move = tt_entry.and_then(|entry| entry.best_move);
+    let killer_moves = search_info.killer_moves[ply_from_root as usize];
+    let counter_move = search_info.history.counter_move(ply_from_root);
+    moves.sort_by_cached_key(|&mov| {
+        let score = if Some(mov) == pv_move {
             // consider principle variation moves before anything else
-            mov.order_heuristic = POS_INF;
+            POS_INF
+        } else if Some(mov) == hash_move {
+            HASH_MOVE_SCORE
+        } else if killer_moves.contains(&Some(mov)) {
+            // consider killer moves after considering good captures
+            KILLER_MOVE_SCORE
+        } else if mov.is_capture() || mov.promotion_kind().is_some() {
+            capture_score(board, mov)
+        } else if Some(mov) == counter_move {
+            COUNTER_MOVE_SCORE
         } else {
-            for i in 0..KILLER_MOVE_PLY_SIZE {
-                if mov.last_move == search_info.killer_moves[ply_from_root as usize][i] {
-                    // consider killer moves after considering "good" captures
-                    mov.order_heuristic = KILLER_MOVE_SCORE;
-                    break;
-                }
-            }
+            QUIET_MOVE_OFFSET + search_info.history.quiet_score(board, ply_from_root, mov)
+        };
+        Reverse(score)
+    });
+
+    let original_alpha = alpha;
+    let mut best_score = NEG_INF;
+    let mut best_move = moves[0];
+    let late_move_count = search_info.params.late_move_count(depth);
+    // the most a quiet move could raise the static evaluation by this close to the horizon
+    let futility_value = static_eval
+        .zip(search_info.params.futility_margin(depth))
+        .map(|(eval, margin)| eval + margin);
+
+    // Singular extensions https://www.chessprogramming.org/Singular_Extensions
+    // the hash move is searched deeper when no other move comes close to its score
+    let singular_move = match tt_entry {
+        Some(entry)
+            if ply_from_root > 0
+                && depth >= SINGULAR_MIN_DEPTH
+                && entry.depth + 3 >= depth
+                && entry.bound != Bound::Upper
+                && entry.score.abs() < MATE_THRESHOLD =>
+        {
+            entry.best_move.filter(|&hash_move| {
+                is_singular(
+                    start,
+                    time_to_move_ms,
+                    board,
+                    depth,
+                    ply_from_root,
+                    entry.score - SINGULAR_MARGIN * depth as i32,
+                    hash_move,
+                    &moves,
+                    search_info,
+                    zobrist_hasher,
+                    draw_table,
+                    transposition_table,
+                )
+            })
         }
-    }
+        _ => None,
+    };
 
-    moves.sort_unstable_by_key(|k| Reverse(k.order_heuristic));
-    search_info.insert_into_cur_line(ply_from_root, &moves[0]);
-    if moves[0].order_heuristic != POS_INF {
-        search_info.set_principle_variation();
-    }
+    // quiet moves that didn't cause a cutoff, they are penalised if a later one does
+    let mut quiets_searched = Vec::new();
+    for (i, &mov) in moves.iter().enumerate() {
+        let is_quiet_move = !mov.is_capture() && mov.promotion_kind().is_none();
+        search_info.history.push_move(board, ply_from_root, mov);
+        let undo = board.make_move(mov, zobrist_hasher);
+        let gives_check = is_check(board, board.to_move);
+        // quiet moves that don't give check are the ones ordered late for a reason
+        let is_quiet =
+            !in_check && is_quiet_move && !killer_moves.contains(&Some(mov)) && !gives_check;
 
-    // do a full search with what we think is the best move
-    // which should be the first move in the array
-    let mut best_score = -alpha_beta_search(
-        start,
-        time_to_move_ms,
-        &moves[0],
-        depth - 1,
-        ply_from_root + 1,
-        -beta,
-        -alpha,
-        search_info,
-        true,
-        zobrist_hasher,
-        draw_table,
-    );
+        // Late move pruning, by now a quiet move is unlikely to be any better than what we have
+        if is_quiet && !is_pv_node && late_move_count.is_some_and(|count| i >= count) {
+            board.unmake_move(mov, &undo);
+            continue;
+        }
 
-    if best_score > alpha {
-        if best_score >= beta {
-            draw_table.remove_board_from_draw_table(board);
-            return best_score;
+        // Futility pruning, this quiet move can't bring the score back up to alpha
+        if is_quiet && i > 0 && futility_value.is_some_and(|value| value <= alpha) {
+            board.unmake_move(mov, &undo);
+            continue;
         }
-        search_info.set_principle_variation();
-        alpha = best_score;
-    }
 
-    // https://en.wikipedia.org/wiki/Principal_variation_search
-    // try out all remaining moves with a reduced window
-    for mov in moves.iter().skip(1) {
-        search_info.insert_into_cur_line(ply_from_root, mov);
-        // zero window search
-        let mut score = -alpha_beta_search(
-            start,
-            time_to_move_ms,
-            mov,
-            depth - 1,
-            ply_from_root + 1,
-            -alpha - 1,
-            -alpha,
-            search_info,
-            true,
-            zobrist_hasher,
-            draw_table,
-        );
+        // Check extensions, a move that gives check is searched one ply deeper
+        let extension = if Some(mov) == singular_move || gives_check {
+            1
+        } else {
+            0
+        };
+        let new_depth = depth - 1 + search_info.extend_line(ply_from_root, depth, extension);
 
-        if score > alpha && score < beta {
-            // got a result outside our window, need to redo full search
+        let mut score;
+        if i == 0 {
+            // do a full search with what we think is the best move
+            // which should be the first move in the array
             score = -alpha_beta_search(
                 start,
                 time_to_move_ms,
-                mov,
-                depth - 1,
+                board,
+                new_depth,
                 ply_from_root + 1,
                 -beta,
                 -alpha,
@@ -233,186 +420,1363 @@ fn alpha_beta_search(
                 true,
                 zobrist_hasher,
                 draw_table,
+                transposition_table,
+            );
+        } else {
+            // https://en.wikipedia.org/wiki/Principal_variation_search
+            // try out all remaining moves with a zero window, late quiet moves at a reduced depth
+            let reduction = if is_quiet {
+                search_info.params.late_move_reduction(depth, i)
+            } else {
+                0
+            };
+            score = -alpha_beta_search(
+                start,
+                time_to_move_ms,
+                board,
+                new_depth - reduction,
+                ply_from_root + 1,
+                -alpha - 1,
+                -alpha,
+                search_info,
+                true,
+                zobrist_hasher,
+                draw_table,
+                transposition_table,
             );
 
-            if score > alpha {
-                alpha = score;
+            if reduction > 0 && score > alpha {
+                // the reduced search beat alpha, make sure at the full depth
+                score = -alpha_beta_search(
+                    start,
+                    time_to_move_ms,
+                    board,
+                    new_depth,
+                    ply_from_root + 1,
+                    -alpha - 1,
+                    -alpha,
+                    search_info,
+                    true,
+                    zobrist_hasher,
+                    draw_table,
+                    transposition_table,
+                );
+            }
+
+            if score > alpha && score < beta {
+                // got a result outside our window, need to redo full search
+                score = -alpha_beta_search(
+                    start,
+                    time_to_move_ms,
+                    board,
+                    new_depth,
+                    ply_from_root + 1,
+                    -beta,
+                    -alpha,
+                    search_info,
+                    true,
+                    zobrist_hasher,
+                    draw_table,
+                    transposition_table,
+                );
             }
         }
 
+        board.unmake_move(mov, &undo);
+
         if score > best_score {
-            if score >= beta {
-                // avoid inserting PV nodes or captures into the killer moves table
-                if mov.order_heuristic == 0 {
-                    search_info.insert_killer_move(ply_from_root, mov);
+            best_score = score;
+            best_move = mov;
+            if score > alpha {
+                if score >= beta {
+                    // avoid inserting captures into the killer moves table
+                    if is_quiet_move {
+                        search_info.insert_killer_move(ply_from_root, mov);
+                        search_info.history.update(
+                            board,
+                            ply_from_root,
+                            depth,
+                            mov,
+                            &quiets_searched,
+                        );
+                    }
+                    search_info.record_cutoff(i);
+                    draw_table.remove_board_from_draw_table(board);
+                    store_search_result(
+                        transposition_table,
+                        board,
+                        depth,
+                        ply_from_root,
+                        Bound::Lower,
+                        score,
+                        mov,
+                        search_info,
+                        start,
+                        time_to_move_ms,
+                    );
+                    return score;
                 }
-                draw_table.remove_board_from_draw_table(board);
-                return score;
+                search_info.update_pv(ply_from_root, mov);
+                alpha = score;
             }
-            search_info.set_principle_variation();
-            best_score = score;
+        }
+        if is_quiet_move {
+            quiets_searched.push(mov);
         }
     }
 
     draw_table.remove_board_from_draw_table(board);
 
+    let bound = if best_score > original_alpha {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    };
+    store_search_result(
+        transposition_table,
+        board,
+        depth,
+        ply_from_root,
+        bound,
+        best_score,
+        best_move,
+        search_info,
+        start,
+        time_to_move_ms,
+    );
+
     best_score
 }
 
+// R grows with depth, a deep null move search can skip more of its subtree
+fn null_move_reduction(depth: u8) -> u8 {
+    2 + depth / 6
+}
+
 /*
-    Interface to the alpha_beta function, works very similarly but returns a board state at the end
-    and also operates with a channel to send the best board state found so far
+    Search every move other than the hash move at half the depth with a zero window
+    just below singular_beta, the hash move is singular if none of them reach it
 */
-pub fn get_best_move(
-    board: &BoardState,
+#[allow(clippy::too_many_arguments)]
+fn is_singular(
+    start: Instant,
+    time_to_move_ms: u128,
+    board: &mut BoardState,
+    depth: u8,
+    ply_from_root: i32,
+    singular_beta: i32,
+    hash_move: Move,
+    moves: &[Move],
+    search_info: &mut Search,
+    zobrist_hasher: &ZobristHasher,
     draw_table: &mut DrawTable,
+    transposition_table: &TranspositionTable,
+) -> bool {
+    if !moves.contains(&hash_move) {
+        return false;
+    }
+    for &mov in moves.iter().filter(|&&mov| mov != hash_move) {
+        search_info.history.push_move(board, ply_from_root, mov);
+        search_info.extend_line(ply_from_root, depth, 0);
+        let undo = board.make_move(mov, zobrist_hasher);
+        let score = -alpha_beta_search(
+            start,
+            time_to_move_ms,
+            board,
+            (depth - 1) / 2,
+            ply_from_root + 1,
+            -singular_beta,
+            -singular_beta + 1,
+            search_info,
+            true,
+            zobrist_hasher,
+            draw_table,
+            transposition_table,
+        );
+        board.unmake_move(mov, &undo);
+        if score >= singular_beta {
+            return false;
+        }
+    }
+    true
+}
+
+/*
+    Record the result of searching a position in the transposition table
+    Results from a search that ran out of time are incomplete so they are thrown away
+*/
+#[allow(clippy::too_many_arguments)]
+fn store_search_result(
+    transposition_table: &TranspositionTable,
+    board: &BoardState,
+    depth: u8,
+    ply_from_root: i32,
+    bound: Bound,
+    score: i32,
+    best_move: Move,
+    search_info: &Search,
     start: Instant,
     time_to_move_ms: u128,
-    tx: &BoardSender,
 ) {
-    let mut cur_depth = 1;
-    let ply_from_root = 0;
-    let mut best_move: Option<BoardState> = None;
+    if search_info.should_stop(start, time_to_move_ms) {
+        return;
+    }
 
-    let mut search_info = Search::new_search();
+    // a fail low doesn't tell us which move is best
+    let best_move = if bound == Bound::Upper {
+        None
+    } else {
+        Some(best_move)
+    };
+    transposition_table.store(
+        board.zobrist_key,
+        depth,
+        bound,
+        score,
+        best_move,
+        ply_from_root,
+    );
+}
+
+/*
+    Interface to the alpha_beta function, works very similarly but also operates
+    with a channel to send the best move found so far
+
+    Uses Lazy SMP when given more than one thread, https://www.chessprogramming.org/Lazy_SMP
+    Every thread searches the same root sharing only the transposition table, so the
+    helper threads mostly serve to fill the table with results the main thread can use
+
+    Each thread orders quiet moves with its own history, which is kept in histories between
+    searches so the next move of a game starts from what this one learned
+
+    Returns once any of the limits is reached, the search is aborted through shared,
+    or every depth has been searched
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn get_best_move(
+    board: &BoardState,
+    draw_table: &mut DrawTable,
+    start: Instant,
+    limits: &SearchLimits,
+    threads: usize,
+    shared: &Arc<SharedSearch>,
+    transposition_table: &TranspositionTable,
+    histories: &mut Vec<History>,
+    tx: &MoveSender,
+) {
     let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+    transposition_table.new_search();
+    histories.resize_with(threads, History::default);
+    histories.iter_mut().for_each(History::age);
+    let mut thread_histories = std::mem::take(histories).into_iter();
+    let main_history = thread_histories.next().unwrap();
+
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads)
+            .zip(thread_histories)
+            .map(|(thread_id, history)| {
+                let mut draw_table = draw_table.clone();
+                let mut search_info = Search::new_search(Arc::clone(shared), thread_id, history);
+                let zobrist_hasher = &zobrist_hasher;
+                scope.spawn(move || {
+                    let result = iterative_deepening(
+                        board,
+                        &mut draw_table,
+                        start,
+                        limits,
+                        &mut search_info,
+                        zobrist_hasher,
+                        transposition_table,
+                        None,
+                    );
+                    (result, search_info.history)
+                })
+            })
+            .collect();
 
-    let mut moves = generate_moves(board, MoveGenerationMode::AllMoves, &zobrist_hasher);
-
-    while cur_depth < MAX_DEPTH {
-        let mut alpha = NEG_INF;
-        let beta = POS_INF;
-        search_info.reset_search();
-        moves.sort_unstable_by_key(|k| Reverse(k.order_heuristic));
-        for mov in &moves {
-            // make an effort to exit once we are out of time
-            if out_of_time(start, time_to_move_ms) {
-                // if we have not found a move to send back, send back the best move as determined by the order_heuristic
-                // this can happen on very short time control situations
-                if best_move.is_none() {
-                    tx.send(moves[0].clone()).unwrap();
+        let mut search_info = Search::new_search(Arc::clone(shared), 0, main_history);
+        let main_result = iterative_deepening(
+            board,
+            draw_table,
+            start,
+            limits,
+            &mut search_info,
+            &zobrist_hasher,
+            transposition_table,
+            Some(tx),
+        );
+        // the main thread may have finished every depth without hitting a limit
+        search_info.stop();
+        histories.push(search_info.history);
+
+        // a helper that finished a deeper iteration than the main thread found the better move,
+        // a depth that was stopped partway through may not have searched the best move yet
+        let mut deepest = main_result.completed;
+        let mut helper_result = None;
+        for helper in helpers {
+            let Ok((result, history)) = helper.join() else {
+                histories.push(History::default());
+                continue;
+            };
+            histories.push(history);
+            if let Some(result) = result.completed {
+                if deepest.is_none_or(|best| result.depth > best.depth) {
+                    deepest = Some(result);
+                    helper_result = Some(result);
                 }
-                return;
             }
+        }
+        if let Some(result) = helper_result {
+            if main_result
+                .best
+                .is_none_or(|main| main.best_move != result.best_move)
+            {
+                tx.send(result).unwrap();
+            }
+        }
+    });
+}
 
-            let evaluation = -alpha_beta_search(
-                start,
-                time_to_move_ms,
-                mov,
-                cur_depth - 1,
-                ply_from_root + 1,
-                -beta,
-                -alpha,
-                &mut search_info,
-                true,
-                &zobrist_hasher,
-                draw_table,
+/*
+    Look for a forced mate in at most max_moves moves with the mate solver, the search
+    limits for nodes, time and root moves still apply and the proof tree is kept within
+    hash_size_mb megabytes
+
+    A mate found is sent down the channel like any other search result, along with an
+    info line holding the whole mating line
+*/
+pub fn find_forced_mate(
+    board: &BoardState,
+    start: Instant,
+    limits: &SearchLimits,
+    max_moves: u8,
+    hash_size_mb: usize,
+    shared: &SharedSearch,
+    tx: &MoveSender,
+) -> MateResult {
+    let mut solver = MateSolver::new(board, limits, hash_size_mb, start, shared);
+    let result = solver.solve(board, max_moves);
+    let time_ms = Instant::now().duration_since(start).as_millis();
+    match &result {
+        MateResult::Mate(line) => {
+            let plies = line.len() as u8;
+            let mut info = format!(
+                "info depth {} score mate {} nodes {} time {} pv",
+                plies,
+                plies.div_ceil(2),
+                solver.nodes_searched(),
+                time_ms
             );
+            for mov in line {
+                info += &format!(" {}", mov);
+            }
+            send_to_gui(&info);
+            tx.send(SearchResult {
+                best_move: line[0],
+                ponder_move: line.get(1).copied(),
+                score: MATE_SCORE - plies as i32,
+                depth: plies,
+            })
+            .unwrap();
+        }
+        MateResult::NoMate => send_to_gui(&format!("info string no mate in {}", max_moves)),
+        MateResult::Unknown => send_to_gui(&format!(
+            "info string mate search stopped after {} nodes",
+            solver.nodes_searched()
+        )),
+    }
+    result
+}
 
-            search_info.insert_into_cur_line(ply_from_root, mov);
+/*
+    One of the best lines found at the root, with MultiPV more than one is searched
+*/
+#[derive(Copy, Clone)]
+struct PvLine {
+    score: i32,
+    pv: MoveArray,
+}
+
+/*
+    The best move a thread found, which may come from a depth it was stopped partway
+    through, and the best move of the deepest iteration it finished
+*/
+#[derive(Copy, Clone, Default)]
+struct ThreadResult {
+    best: Option<SearchResult>,
+    completed: Option<SearchResult>,
+}
+
+/*
+    Search deeper and deeper until a limit is reached, only the main thread sends
+    its results as they are found. Returns the best moves this thread found
+
+    Helper threads start at different depths so they aren't all searching the same
+    positions at the same time
+
+    With MultiPV each depth searches the root once per line, leaving out the moves
+    of the lines already found so every line has its own score and principle variation
+*/
+#[allow(clippy::too_many_arguments)]
+fn iterative_deepening(
+    board: &BoardState,
+    draw_table: &mut DrawTable,
+    start: Instant,
+    limits: &SearchLimits,
+    search_info: &mut Search,
+    zobrist_hasher: &ZobristHasher,
+    transposition_table: &TranspositionTable,
+    tx: Option<&MoveSender>,
+) -> ThreadResult {
+    let time_to_move_ms = limits.time_to_move_ms(board.to_move);
+    let mut cur_depth = 1 + (search_info.thread_id % 3) as u8;
+    let ply_from_root = 0;
+    let mut best_result: Option<SearchResult> = None;
+    let mut completed_result: Option<SearchResult> = None;
+    let mut board = board.clone();
+    search_info.node_limit = limits.nodes;
 
-            if evaluation > alpha && !out_of_time(start, time_to_move_ms) {
-                //alpha raised, remember this line as the pv
-                alpha = evaluation;
-                best_move = Some(mov.clone());
-                tx.send(mov.clone()).unwrap();
-                search_info.set_principle_variation();
-                send_search_info(&search_info, cur_depth, evaluation, start);
+    let mut moves = generate_moves(&board, MoveGenerationMode::AllMoves);
+    if !limits.search_moves.is_empty() {
+        moves.retain(|mov| limits.search_moves.contains(mov));
+    }
+    if moves.is_empty() {
+        return ThreadResult::default();
+    }
+
+    // a previous search may have already told us which move is best here
+    let hash_move = transposition_table
+        .probe(board.zobrist_key, ply_from_root)
+        .and_then(|entry| entry.best_move);
+    moves.sort_by_cached_key(|&mov| {
+        let score = if Some(mov) == hash_move {
+            HASH_MOVE_SCORE
+        } else if mov.is_capture() || mov.promotion_kind().is_some() {
+            capture_score(&board, mov)
+        } else {
+            0
+        };
+        Reverse(score)
+    });
+
+    // only the main thread reports every line, the helpers just look for the best one
+    let multi_pv = if search_info.is_main_thread() {
+        limits.multi_pv.clamp(1, moves.len())
+    } else {
+        1
+    };
+    let mut lines: Vec<PvLine> = Vec::new();
+
+    'search: while cur_depth <= limits.depth {
+        let mut depth_lines: Vec<PvLine> = Vec::new();
+        for pv_index in 0..multi_pv {
+            // the same line from the last depth is the best guess for this one
+            search_info.pv_moves = lines
+                .get(pv_index)
+                .map_or([None; MAX_DEPTH as usize], |line| line.pv);
+            let previous_move = search_info.pv_moves[0];
+            let mut root_moves: Vec<Move> = moves
+                .iter()
+                .filter(|&&mov| depth_lines.iter().all(|line| line.pv[0] != Some(mov)))
+                .copied()
+                .collect();
+            // found the pv node, the rest keep their order
+            root_moves.sort_by_key(|&mov| Some(mov) != previous_move);
+
+            // Aspiration windows https://www.chessprogramming.org/Aspiration_Windows
+            // expect the score to stay close to the last depth, widening whichever side fails
+            let mut delta = ASPIRATION_WINDOW;
+            let (mut window_alpha, mut beta) = match lines.get(pv_index) {
+                Some(line)
+                    if cur_depth >= ASPIRATION_MIN_DEPTH && line.score.abs() < MATE_THRESHOLD =>
+                {
+                    (line.score - delta, line.score + delta)
+                }
+                _ => (NEG_INF, POS_INF),
+            };
+            let line = loop {
+                let mut alpha = window_alpha;
+                let mut line: Option<PvLine> = None;
+                search_info.reset_search();
+                for &mov in &root_moves {
+                    // make an effort to exit once we are out of time
+                    if search_info.should_stop(start, time_to_move_ms) {
+                        break 'search;
+                    }
+
+                    search_info.history.push_move(&board, ply_from_root, mov);
+                    let undo = board.make_move(mov, zobrist_hasher);
+                    let gives_check = is_check(&board, board.to_move);
+                    let extension =
+                        search_info.extend_line(ply_from_root, cur_depth, gives_check as u8);
+                    let evaluation = -alpha_beta_search(
+                        start,
+                        time_to_move_ms,
+                        &mut board,
+                        cur_depth - 1 + extension,
+                        ply_from_root + 1,
+                        -beta,
+                        -alpha,
+                        search_info,
+                        true,
+                        zobrist_hasher,
+                        draw_table,
+                        transposition_table,
+                    );
+                    board.unmake_move(mov, &undo);
+
+                    if evaluation > alpha && !search_info.should_stop(start, time_to_move_ms) {
+                        //alpha raised, remember this line as the pv
+                        alpha = evaluation;
+                        search_info.update_pv(ply_from_root, mov);
+                        let pv = search_info.principle_variation();
+                        debug_assert!(
+                            is_legal_line(&board, &pv, zobrist_hasher),
+                            "Illegal principle variation from {}",
+                            board.to_fen()
+                        );
+                        line = Some(PvLine {
+                            score: evaluation,
+                            pv,
+                        });
+                        // the rest of the moves can't do better than a fail high
+                        if evaluation >= beta {
+                            break;
+                        }
+                        if pv_index > 0 {
+                            continue;
+                        }
+                        let result = SearchResult {
+                            best_move: mov,
+                            ponder_move: pv[1],
+                            score: evaluation,
+                            depth: cur_depth,
+                        };
+                        best_result = Some(result);
+                        if let Some(tx) = tx {
+                            tx.send(result).unwrap();
+                            if multi_pv == 1 {
+                                send_search_info(
+                                    search_info,
+                                    cur_depth,
+                                    None,
+                                    &pv,
+                                    evaluation,
+                                    Bound::Exact,
+                                    start,
+                                );
+                            }
+                        }
+                    }
+                }
+
+                let line_number = (multi_pv > 1).then_some(pv_index + 1);
+                match line {
+                    Some(line) if line.score >= beta => {
+                        // fail high, the move that beat beta is likely the best so try it first
+                        if pv_index == 0 {
+                            let result = SearchResult {
+                                best_move: line.pv[0].unwrap(),
+                                ponder_move: line.pv[1],
+                                score: line.score,
+                                depth: cur_depth,
+                            };
+                            best_result = Some(result);
+                            if let Some(tx) = tx {
+                                tx.send(result).unwrap();
+                            }
+                        }
+                        if tx.is_some() {
+                            send_search_info(
+                                search_info,
+                                cur_depth,
+                                line_number,
+                                &line.pv,
+                                line.score,
+                                Bound::Lower,
+                                start,
+                            );
+                        }
+                        root_moves.sort_by_key(|&mov| Some(mov) != line.pv[0]);
+                        search_info.pv_moves = line.pv;
+                        beta = (beta + delta).min(POS_INF);
+                    }
+                    None if window_alpha > NEG_INF => {
+                        // fail low, every move is worse than the last depth expected
+                        if tx.is_some() {
+                            send_search_info(
+                                search_info,
+                                cur_depth,
+                                line_number,
+                                &search_info.pv_moves,
+                                window_alpha,
+                                Bound::Upper,
+                                start,
+                            );
+                        }
+                        // the score is expected below the old window now, so bring beta down too
+                        beta = (window_alpha + beta) / 2;
+                        window_alpha = (window_alpha - delta).max(NEG_INF);
+                    }
+                    line => break line,
+                }
+                delta *= 2;
+            };
+
+            if let Some(line) = line {
+                if multi_pv > 1 && tx.is_some() {
+                    send_search_info(
+                        search_info,
+                        cur_depth,
+                        Some(pv_index + 1),
+                        &line.pv,
+                        line.score,
+                        Bound::Exact,
+                        start,
+                    );
+                }
+                depth_lines.push(line);
             }
         }
-        moves = generate_moves(board, MoveGenerationMode::AllMoves, &zobrist_hasher);
-        if let Some(b) = &best_move {
-            for mov in &mut moves {
-                if mov.last_move == b.last_move {
-                    // found the pv node
-                    mov.order_heuristic = POS_INF;
-                    break;
-                }
+        lines = depth_lines;
+        // the last root move may have been cut short by the search stopping
+        if !search_info.should_stop(start, time_to_move_ms) {
+            completed_result = best_result;
+        }
+
+        if let Some(result) = best_result {
+            store_search_result(
+                transposition_table,
+                &board,
+                cur_depth,
+                ply_from_root,
+                Bound::Exact,
+                result.score,
+                result.best_move,
+                search_info,
+                start,
+                time_to_move_ms,
+            );
+            if search_info.is_main_thread() && limits.is_mate_found(mate_in_plies(result.score)) {
+                break;
             }
         }
         cur_depth += 1;
     }
+    search_info.flush_nodes();
+    if tx.is_some() {
+        send_ordering_stats(search_info);
+    }
+
+    // if we have not found a move to send back, send back the best move as determined by the order_heuristic
+    // this can happen on very short time control situations
+    if best_result.is_none() && search_info.is_main_thread() {
+        let result = SearchResult {
+            best_move: moves[0],
+            ponder_move: None,
+            score: 0,
+            depth: 0,
+        };
+        if let Some(tx) = tx {
+            tx.send(result).unwrap();
+        }
+        return ThreadResult {
+            best: Some(result),
+            completed: None,
+        };
+    }
+    ThreadResult {
+        best: best_result,
+        completed: completed_result,
+    }
+}
+
+// every move of the line can be played in turn from the board
+fn is_legal_line(board: &BoardState, line: &MoveArray, zobrist_hasher: &ZobristHasher) -> bool {
+    let mut board = board.clone();
+    for mov in line.iter().map_while(|&mov| mov) {
+        if !generate_moves(&board, MoveGenerationMode::AllMoves).contains(&mov) {
+            return false;
+        }
+        board.make_move(mov, zobrist_hasher);
+    }
+    true
+}
+
+// the number of plies until the side to move mates, if the score is a winning mate score
+fn mate_in_plies(score: i32) -> Option<u8> {
+    if score > MATE_THRESHOLD {
+        Some((MATE_SCORE - score) as u8)
+    } else {
+        None
+    }
 }
 
 /*
     Send information about the current search status to the GUI
 */
-fn send_search_info(search_info: &Search, depth: u8, eval: i32, start: Instant) {
-    let mut ponder_move = "".to_string();
-    for mov in &search_info.pv_moves {
-        if let Some(m) = mov {
-            ponder_move = format!("{} {}{}", ponder_move, m.0, m.1)
-        } else {
-            break;
-        }
+fn send_search_info(
+    search_info: &Search,
+    depth: u8,
+    multi_pv: Option<usize>,
+    pv: &MoveArray,
+    eval: i32,
+    bound: Bound,
+    start: Instant,
+) {
+    send_to_gui(&search_info_line(
+        depth,
+        multi_pv,
+        pv,
+        eval,
+        bound,
+        search_info.total_nodes(),
+        Instant::now().duration_since(start).as_millis(),
+    ));
+}
+
+fn search_info_line(
+    depth: u8,
+    multi_pv: Option<usize>,
+    pv: &MoveArray,
+    eval: i32,
+    bound: Bound,
+    nodes: u64,
+    time_ms: u128,
+) -> String {
+    let mut info = "info".to_string();
+    if let Some(line_number) = multi_pv {
+        info += &format!(" multipv {}", line_number);
     }
+    info += &format!(" depth {} score {}", depth, uci_score(eval));
+    // a search that failed outside its aspiration window only knows a bound on the score
+    match bound {
+        Bound::Lower => info += " lowerbound",
+        Bound::Upper => info += " upperbound",
+        Bound::Exact => {}
+    }
+    // the pv goes last as GUIs read every move up to the end of the line
+    info += &format!(" nodes {} time {} pv", nodes, time_ms);
+    for mov in pv.iter().map_while(|&mov| mov) {
+        info += &format!(" {}", mov);
+    }
+    info
+}
 
+// how well the moves were ordered, so changes to the ordering can be compared
+fn send_ordering_stats(search_info: &Search) {
+    let stats = search_info.ordering_stats();
+    send_to_gui(&format!(
+        "info string move ordering {} cutoffs, {:.1}% on the first move, average cutoff move {:.2}",
+        stats.cutoffs,
+        stats.first_move_rate(),
+        stats.average_cutoff_move()
+    ));
+}
+
+// scores are sent in centipawns, or in moves until mate when one has been found
+fn uci_score(eval: i32) -> String {
     let mate_window = 15;
     if eval >= MATE_SCORE - mate_window {
         // this player is threatening checkmate
-        send_to_gui(&format!(
-            "info pv{} depth {} nodes {} score mate {} time {}",
-            ponder_move,
-            depth,
-            search_info.nodes_searched,
-            (MATE_SCORE - eval + 1) / 2,
-            Instant::now().duration_since(start).as_millis()
-        ));
+        format!("mate {}", (MATE_SCORE - eval + 1) / 2)
     } else if eval <= -MATE_SCORE + mate_window {
         // this player is getting matted
-        send_to_gui(&format!(
-            "info pv{} depth {} nodes {} score mate {} time {}",
-            ponder_move,
-            depth,
-            search_info.nodes_searched,
-            (MATE_SCORE + eval) / -2,
-            Instant::now().duration_since(start).as_millis()
-        ));
+        format!("mate {}", (MATE_SCORE + eval) / -2)
     } else {
-        send_to_gui(&format!(
-            "info pv{} depth {} nodes {} score cp {} time {}",
-            ponder_move,
-            depth,
-            search_info.nodes_searched,
-            eval,
-            Instant::now().duration_since(start).as_millis()
-        ));
+        format!("cp {}", eval)
     }
 }
 
+/*
+    Check if the game is over, the draw table must contain every position played so far
+*/
+fn game_result(board: &BoardState, draw_table: &DrawTable) -> Option<GameResult> {
+    if generate_moves(board, MoveGenerationMode::AllMoves).is_empty() {
+        if !is_check(board, board.to_move) {
+            return Some(GameResult::Draw);
+        }
+        return match board.to_move {
+            White => Some(GameResult::BlackWins),
+            Black => Some(GameResult::WhiteWins),
+        };
+    }
+
+    let repetitions = *draw_table.table.get(&board.zobrist_key).unwrap_or(&0);
+    if repetitions >= 3
+        || is_fifty_move_draw(board)
+        || MaterialSignature::from_board(board).is_insufficient_material()
+    {
+        return Some(GameResult::Draw);
+    }
+
+    None
+}
+
 /*
     Play a game in the terminal where the engine plays against itself
+
+    Returns a record of the game, optionally with the score and depth of each move
 */
 pub fn play_game_against_self(
     b: &BoardState,
     max_moves: u8,
     time_to_move_ms: u128,
     simple_print: bool,
-) {
+    eval_comments: bool,
+) -> PgnGame {
     let show_board = |simple_print: bool, b: &BoardState| {
         if simple_print {
             b.simple_print_board()
         } else {
             b.pretty_print_board()
         }
+        println!("{}", b.to_fen());
     };
 
     let mut board = b.clone();
-    let draw_table: DrawTable = DrawTable::new();
+    let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+    let mut draw_table: DrawTable = DrawTable::new();
+    draw_table.add_board_to_draw_table(&board);
+    let transposition_table = TranspositionTable::new(DEFAULT_HASH_SIZE_MB);
+    let mut histories = Vec::new();
+    let engine_name = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let mut game = PgnGame::new(&board);
+    game.set_tag("Event", "Self play");
+    game.set_tag("Site", "Terminal");
+    game.set_tag("White", &engine_name);
+    game.set_tag("Black", &engine_name);
     show_board(simple_print, &board);
     for _ in 0..max_moves {
+        if game_result(&board, &draw_table).is_some() {
+            break;
+        }
+
         let (tx, rx) = mpsc::channel();
-        let start = Instant::now();
-        let clone = board.clone();
-        let mut draw_clone = draw_table.clone();
-        thread::spawn(move || get_best_move(&clone, &mut draw_clone, start, time_to_move_ms, &tx));
-        while !out_of_time(start, time_to_move_ms) {
-            if let Ok(b) = rx.try_recv() {
-                board = b;
+        let mut search_draw_table = draw_table.clone();
+        get_best_move(
+            &board,
+            &mut search_draw_table,
+            Instant::now(),
+            &SearchLimits::time(time_to_move_ms),
+            DEFAULT_THREADS,
+            &Arc::new(SharedSearch::default()),
+            &transposition_table,
+            &mut histories,
+            &tx,
+        );
+        // the last result sent is the best move found
+        if let Some(result) = rx.try_iter().last() {
+            let mov = result.best_move;
+            let move_number = match board.to_move {
+                White => format!("{}.", board.full_move_number),
+                Black => format!("{}...", board.full_move_number),
+            };
+            println!(
+                "{} {}",
+                move_number,
+                move_to_san(&board, mov, &zobrist_hasher)
+            );
+            let comment = if eval_comments && result.depth > 0 {
+                Some(eval_comment(result.score, result.depth))
             } else {
-                thread::sleep(Duration::from_millis(1));
-            }
+                None
+            };
+            game.add_move(&board, mov, comment, &zobrist_hasher);
+            board.make_move(mov, &zobrist_hasher);
+            draw_table.add_board_to_draw_table(&board);
         }
         show_board(simple_print, &board);
     }
+    game.result = game_result(&board, &draw_table).unwrap_or(GameResult::Unknown);
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchParams;
+    use std::sync::atomic::Ordering;
+
+    fn search(fen: &str, limits: SearchLimits, threads: usize) -> Vec<SearchResult> {
+        let board = BoardState::from_fen(fen).unwrap();
+        let mut draw_table = DrawTable::new();
+        draw_table.add_board_to_draw_table(&board);
+        let transposition_table = TranspositionTable::new(DEFAULT_HASH_SIZE_MB);
+        let (tx, rx) = mpsc::channel();
+        get_best_move(
+            &board,
+            &mut draw_table,
+            Instant::now(),
+            &limits,
+            threads,
+            &Arc::new(SharedSearch::default()),
+            &transposition_table,
+            &mut Vec::new(),
+            &tx,
+        );
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn history_kept_between_searches() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let transposition_table = TranspositionTable::new(DEFAULT_HASH_SIZE_MB);
+        let mut histories = Vec::new();
+        let search = |histories: &mut Vec<History>, depth: u8| {
+            let limits = SearchLimits {
+                depth,
+                ..SearchLimits::default()
+            };
+            let mut draw_table = DrawTable::new();
+            draw_table.add_board_to_draw_table(&board);
+            let (tx, _rx) = mpsc::channel();
+            get_best_move(
+                &board,
+                &mut draw_table,
+                Instant::now(),
+                &limits,
+                2,
+                &Arc::new(SharedSearch::default()),
+                &transposition_table,
+                histories,
+                &tx,
+            );
+        };
+        let scores = |history: &History| -> Vec<i32> {
+            generate_moves(&board, MoveGenerationMode::AllMoves)
+                .into_iter()
+                .map(|mov| history.quiet_score(&board, 0, mov))
+                .collect()
+        };
+
+        // every thread hands back the history it learned
+        search(&mut histories, 5);
+        assert_eq!(histories.len(), 2);
+        let learned = scores(&histories[0]);
+        assert!(learned.iter().any(|&score| score != 0));
+
+        // the next search starts from the same history halved, a depth 1 search has no
+        // cutoffs below the root to add anything to it
+        search(&mut histories, 1);
+        assert_eq!(histories.len(), 2);
+        assert_eq!(
+            scores(&histories[0]),
+            learned.iter().map(|score| score / 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn mate_found_with_helper_threads() {
+        let limits = SearchLimits {
+            depth: 4,
+            ..SearchLimits::default()
+        };
+        let results = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits, 4);
+        let best = results.last().unwrap();
+        assert_eq!(best.best_move.to_string(), "a1a8");
+        assert_eq!(best.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn search_stops_once_mate_found() {
+        // mate in two, the search would otherwise carry on until MAX_DEPTH
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::default()
+        };
+        let results = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", limits, 1);
+        let best = results.last().unwrap();
+        assert_eq!(best.score, MATE_SCORE - 3);
+        assert!(best.depth <= 5);
+    }
+
+    #[test]
+    fn forced_mate_sent_as_result() {
+        let board = BoardState::from_fen("2r3k1/5ppp/8/8/8/8/3R4/3R2K1 w - - 0 1").unwrap();
+        let shared = SharedSearch::default();
+        let (tx, rx) = mpsc::channel();
+        let limits = SearchLimits::default();
+        let result = find_forced_mate(
+            &board,
+            Instant::now(),
+            &limits,
+            2,
+            DEFAULT_HASH_SIZE_MB,
+            &shared,
+            &tx,
+        );
+        assert!(matches!(result, MateResult::Mate(_)));
+        let best = rx.try_iter().last().unwrap();
+        assert_eq!(best.best_move.to_string(), "d2d8");
+        assert_eq!(best.ponder_move.map(|mov| mov.to_string()).unwrap(), "c8d8");
+        assert_eq!(best.score, MATE_SCORE - 3);
+
+        // nothing is sent without a mate
+        let result = find_forced_mate(
+            &board,
+            Instant::now(),
+            &limits,
+            1,
+            DEFAULT_HASH_SIZE_MB,
+            &shared,
+            &tx,
+        );
+        assert_eq!(result, MateResult::NoMate);
+        assert!(rx.try_iter().next().is_none());
+    }
+
+    #[test]
+    fn ponder_move_from_principle_variation() {
+        let limits = SearchLimits {
+            depth: 4,
+            ..SearchLimits::default()
+        };
+        // after Kc7 the only legal reply is Ka7
+        let results = search("k7/8/2K5/8/8/8/8/7R w - - 0 1", limits.clone(), 1);
+        let best = results.last().unwrap();
+        assert_eq!(best.best_move.to_string(), "c6c7");
+        assert_eq!(
+            best.ponder_move.map(|mov| mov.to_string()),
+            Some("a8a7".to_string())
+        );
+
+        // there is no reply to a mate in one
+        let results = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits, 1);
+        assert_eq!(results.last().unwrap().ponder_move, None);
+    }
+
+    #[test]
+    fn multi_pv_keeps_best_move() {
+        let limits = SearchLimits {
+            depth: 3,
+            multi_pv: 3,
+            ..SearchLimits::default()
+        };
+        let results = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits, 2);
+        let best = results.last().unwrap();
+        assert_eq!(best.best_move.to_string(), "a1a8");
+        assert_eq!(best.score, MATE_SCORE - 1);
+        assert_eq!(best.depth, 3);
+
+        // more lines than there are legal moves
+        let limits = SearchLimits {
+            depth: 3,
+            multi_pv: 10,
+            ..SearchLimits::default()
+        };
+        let results = search("k7/8/2Q5/8/8/8/8/7K b - - 0 1", limits, 1);
+        assert!(!results.is_empty());
+    }
+
+    #[test]
+    fn only_search_moves_searched() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let board = BoardState::from_fen(fen).unwrap();
+        let find = |uci: &str| {
+            generate_moves(&board, MoveGenerationMode::AllMoves)
+                .into_iter()
+                .find(|mov| mov.to_string() == uci)
+                .unwrap()
+        };
+        // the mate in one is left out
+        let limits = SearchLimits {
+            depth: 3,
+            search_moves: vec![find("a1a2"), find("g1f1")],
+            ..SearchLimits::default()
+        };
+        for result in search(fen, limits, 2) {
+            assert!(["a1a2", "g1f1"].contains(&result.best_move.to_string().as_str()));
+            assert!(result.score < MATE_SCORE - MAX_DEPTH as i32);
+        }
+
+        let limits = SearchLimits {
+            depth: 3,
+            search_moves: vec![find("a1a8")],
+            ..SearchLimits::default()
+        };
+        let results = search(fen, limits, 1);
+        assert_eq!(results.last().unwrap().best_move.to_string(), "a1a8");
+    }
+
+    #[test]
+    fn full_legal_pv_found() {
+        let board = BoardState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let mut draw_table = DrawTable::new();
+        draw_table.add_board_to_draw_table(&board);
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut search_info =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        let limits = SearchLimits {
+            depth: 5,
+            ..SearchLimits::default()
+        };
+        iterative_deepening(
+            &board,
+            &mut draw_table,
+            Instant::now(),
+            &limits,
+            &mut search_info,
+            &zobrist_hasher,
+            &TranspositionTable::new(DEFAULT_HASH_SIZE_MB),
+            None,
+        );
+        let pv = search_info.principle_variation();
+        assert!(pv.iter().take_while(|mov| mov.is_some()).count() >= 5);
+        assert!(is_legal_line(&board, &pv, &zobrist_hasher));
+    }
+
+    #[test]
+    fn stopped_depth_not_completed() {
+        let board = BoardState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let run = |limits: SearchLimits| {
+            let mut draw_table = DrawTable::new();
+            draw_table.add_board_to_draw_table(&board);
+            let mut search_info =
+                Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+            iterative_deepening(
+                &board,
+                &mut draw_table,
+                Instant::now(),
+                &limits,
+                &mut search_info,
+                &ZobristHasher::create_zobrist_hasher(),
+                &TranspositionTable::new(DEFAULT_HASH_SIZE_MB),
+                None,
+            )
+        };
+
+        let result = run(SearchLimits {
+            depth: 4,
+            ..SearchLimits::default()
+        });
+        assert_eq!(result.completed.unwrap().depth, 4);
+        assert_eq!(result.best.unwrap().depth, 4);
+
+        // the node limit is hit partway through a depth, which doesn't count as completed
+        let result = run(SearchLimits {
+            nodes: 20000,
+            ..SearchLimits::default()
+        });
+        let completed = result.completed.unwrap();
+        let best = result.best.unwrap();
+        // only the first root moves of the last depth were searched
+        assert_eq!(completed.depth + 1, best.depth);
+    }
+
+    #[test]
+    fn search_info_lists_pv_last() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let moves = generate_moves(&board, MoveGenerationMode::AllMoves);
+        let mut pv = [None; MAX_DEPTH as usize];
+        pv[0] = moves.iter().find(|mov| mov.to_string() == "e2e4").copied();
+        pv[1] = moves.iter().find(|mov| mov.to_string() == "d2d4").copied();
+        assert_eq!(
+            search_info_line(4, Some(2), &pv, 15, Bound::Exact, 1200, 30),
+            "info multipv 2 depth 4 score cp 15 nodes 1200 time 30 pv e2e4 d2d4"
+        );
+        assert_eq!(
+            search_info_line(3, None, &pv, MATE_SCORE - 3, Bound::Exact, 10, 0),
+            "info depth 3 score mate 2 nodes 10 time 0 pv e2e4 d2d4"
+        );
+        assert_eq!(uci_score(-MATE_SCORE + 2), "mate -1");
+        assert_eq!(
+            search_info_line(6, None, &pv, 40, Bound::Lower, 5000, 12),
+            "info depth 6 score cp 40 lowerbound nodes 5000 time 12 pv e2e4 d2d4"
+        );
+        assert_eq!(
+            search_info_line(6, Some(1), &pv, -20, Bound::Upper, 5000, 12),
+            "info multipv 1 depth 6 score cp -20 upperbound nodes 5000 time 12 pv e2e4 d2d4"
+        );
+    }
+
+    fn nodes_searched(fen: &str, depth: u8, params: SearchParams) -> u64 {
+        let board = BoardState::from_fen(fen).unwrap();
+        let mut draw_table = DrawTable::new();
+        draw_table.add_board_to_draw_table(&board);
+        let shared = Arc::new(SharedSearch {
+            params,
+            ..SharedSearch::default()
+        });
+        let limits = SearchLimits {
+            depth,
+            ..SearchLimits::default()
+        };
+        let (tx, _rx) = mpsc::channel();
+        get_best_move(
+            &board,
+            &mut draw_table,
+            Instant::now(),
+            &limits,
+            1,
+            &shared,
+            &TranspositionTable::new(DEFAULT_HASH_SIZE_MB),
+            &mut Vec::new(),
+            &tx,
+        );
+        shared.nodes.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn reductions_search_fewer_nodes() {
+        let mut no_reductions = SearchParams::default();
+        no_reductions.set("LmrMinDepth", 20).unwrap();
+        no_reductions.set("LmpMaxDepth", 0).unwrap();
+        assert!(
+            nodes_searched(DEFAULT_FEN_STRING, 6, SearchParams::default())
+                < nodes_searched(DEFAULT_FEN_STRING, 6, no_reductions)
+        );
+    }
+
+    #[test]
+    fn forward_pruning_searches_fewer_nodes() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut no_pruning = SearchParams::default();
+        no_pruning.set("RfpMaxDepth", 0).unwrap();
+        no_pruning.set("FutilityMaxDepth", 0).unwrap();
+        no_pruning.set("RazorMaxDepth", 0).unwrap();
+        assert!(
+            nodes_searched(fen, 5, SearchParams::default()) < nodes_searched(fen, 5, no_pruning)
+        );
+    }
+
+    #[test]
+    fn forward_pruning_keeps_mates() {
+        // white is a queen for a rook behind, so every other move fails low
+        let limits = SearchLimits {
+            depth: 4,
+            ..SearchLimits::default()
+        };
+        let best = search("6k1/5ppp/8/8/8/8/q4PPP/3R2K1 w - - 0 1", limits, 1)
+            .pop()
+            .unwrap();
+        assert_eq!(best.best_move.to_string(), "d1d8");
+        assert_eq!(best.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn checks_extended_past_the_horizon() {
+        // Rd8+ Rxd8 Rxd8# is only seen at depth 2 because both checks are extended
+        let limits = SearchLimits {
+            depth: 2,
+            ..SearchLimits::default()
+        };
+        let best = search("2r3k1/5ppp/8/8/8/8/3R4/3R2K1 w - - 0 1", limits, 1)
+            .pop()
+            .unwrap();
+        assert_eq!(best.best_move.to_string(), "d2d8");
+        assert_eq!(best.score, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn singular_hash_move_found() {
+        let singular = |fen: &str, hash_move: &str, singular_beta: i32| {
+            let mut board = BoardState::from_fen(fen).unwrap();
+            let moves = generate_moves(&board, MoveGenerationMode::AllMoves);
+            let hash_move = *moves
+                .iter()
+                .find(|mov| mov.to_string() == hash_move)
+                .unwrap();
+            let mut draw_table = DrawTable::new();
+            let mut search_info =
+                Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+            is_singular(
+                Instant::now(),
+                u128::MAX,
+                &mut board,
+                8,
+                1,
+                singular_beta,
+                hash_move,
+                &moves,
+                &mut search_info,
+                &ZobristHasher::create_zobrist_hasher(),
+                &mut draw_table,
+                &TranspositionTable::new(DEFAULT_HASH_SIZE_MB),
+            )
+        };
+        // only taking the queen wins material
+        let fen = "r1b1kbnr/pppp1ppp/2n5/4p1q1/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1";
+        assert!(singular(fen, "f3g5", 500));
+        // plenty of moves keep the start position level
+        assert!(!singular(DEFAULT_FEN_STRING, "e2e4", -50));
+    }
+
+    #[test]
+    fn pawn_ending_zugzwang_found() {
+        // white wins whoever is to move, but only by putting black in zugzwang, which a
+        // null move for black would hide
+        let limits = SearchLimits {
+            depth: 10,
+            ..SearchLimits::default()
+        };
+        let best = search("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", limits, 1)
+            .pop()
+            .unwrap();
+        assert!(best.score > 500, "score {}", best.score);
+    }
+
+    #[test]
+    fn zugzwang_with_pieces_found() {
+        // zugzwang.002 from https://www.chessprogramming.org/Null_Move_Test-Positions
+        let limits = SearchLimits {
+            depth: 6,
+            ..SearchLimits::default()
+        };
+        let best = search("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1", limits, 1)
+            .pop()
+            .unwrap();
+        assert_eq!(best.best_move.to_string(), "g5h6");
+    }
+
+    #[test]
+    fn null_move_reduction_grows_with_depth() {
+        assert_eq!(null_move_reduction(3), 2);
+        assert_eq!(null_move_reduction(6), 3);
+        assert_eq!(null_move_reduction(12), 4);
+        // the null move search never drops below depth 0
+        for depth in 3..MAX_DEPTH {
+            assert!(null_move_reduction(depth) < depth);
+        }
+    }
+
+    #[test]
+    fn fixed_node_searches_repeatable() {
+        let limits = SearchLimits {
+            nodes: 20_000,
+            ..SearchLimits::default()
+        };
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let first: Vec<(Move, i32, u8)> = search(fen, limits.clone(), 1)
+            .iter()
+            .map(|result| (result.best_move, result.score, result.depth))
+            .collect();
+        let second: Vec<(Move, i32, u8)> = search(fen, limits, 1)
+            .iter()
+            .map(|result| (result.best_move, result.score, result.depth))
+            .collect();
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn helper_threads_stop_with_main_thread() {
+        let start = Instant::now();
+        let results = search(DEFAULT_FEN_STRING, SearchLimits::time(200), 3);
+        assert!(!results.is_empty());
+        assert!(start.elapsed().as_millis() < 1000);
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        // stalemate
+        let results = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", SearchLimits::time(100), 2);
+        assert!(results.is_empty());
+    }
 }
@@ -0,0 +1,318 @@
+pub use crate::board::*;
+use crate::draw_table::DrawTable;
+use crate::engine::{get_best_move, SearchResult};
+use crate::notation::{move_to_san, san_to_move};
+use crate::search::{SearchLimits, SharedSearch, DEFAULT_THREADS};
+use crate::transposition_table::{TranspositionTable, DEFAULT_HASH_SIZE_MB};
+use crate::zobrist::ZobristHasher;
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+
+/*
+    A test position from an EPD file, see https://www.chessprogramming.org/Extended_Position_Description
+    Only the operations needed to score a test suite are kept, ex
+
+    r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - bm Bc5; id "sample.002";
+*/
+pub struct EpdPosition {
+    pub board: BoardState,
+    pub id: Option<String>,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+}
+
+impl EpdPosition {
+    pub fn from_epd(epd: &str) -> Result<EpdPosition, &'static str> {
+        // the four position fields, the operations are everything after them
+        let mut fields = Vec::new();
+        let mut operations = epd.trim();
+        while fields.len() < 4 && !operations.is_empty() {
+            let (field, rest) = operations
+                .split_once(char::is_whitespace)
+                .unwrap_or((operations, ""));
+            fields.push(field);
+            operations = rest.trim_start();
+        }
+        if fields.len() < 4 {
+            return Err("Could not parse epd string: Missing position fields");
+        }
+        // EPD has no move counters, they can be given with the hmvc and fmvn operations instead
+        let board = BoardState::from_fen(&format!("{} 0 1", fields.join(" ")))?;
+
+        let mut position = EpdPosition {
+            board,
+            id: None,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+        };
+        for operation in split_operations(operations) {
+            let (opcode, operands) = match operation.split_once(char::is_whitespace) {
+                Some((opcode, operands)) => (opcode, operands.trim()),
+                None => (operation.as_str(), ""),
+            };
+            match opcode {
+                "id" => position.id = Some(operands.trim_matches('"').to_string()),
+                "bm" => position.best_moves = parse_moves(&position.board, operands)?,
+                "am" => position.avoid_moves = parse_moves(&position.board, operands)?,
+                "hmvc" => {
+                    position.board.half_move_clock = operands
+                        .parse()
+                        .map_err(|_| "Could not parse epd string: Invalid hmvc value")?
+                }
+                "fmvn" => {
+                    position.board.full_move_number = operands
+                        .parse()
+                        .map_err(|_| "Could not parse epd string: Invalid fmvn value")?
+                }
+                // other operations such as comments are not needed to score a position
+                _ => {}
+            }
+        }
+        Ok(position)
+    }
+
+    /*
+        A move passes if it is one of the best moves, and none of the moves to avoid
+        A position can have either operation, or both
+    */
+    pub fn is_solved_by(&self, mov: Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&mov))
+            && !self.avoid_moves.contains(&mov)
+    }
+}
+
+// operations are separated by semicolons, which may also appear inside quoted operands
+fn split_operations(operations: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut operation = String::new();
+    let mut in_quotes = false;
+    for c in operations.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                operation.push(c);
+            }
+            ';' if !in_quotes => {
+                result.push(operation.trim().to_string());
+                operation.clear();
+            }
+            _ => operation.push(c),
+        }
+    }
+    if !operation.trim().is_empty() {
+        result.push(operation.trim().to_string());
+    }
+    result.retain(|operation| !operation.is_empty());
+    result
+}
+
+fn parse_moves(board: &BoardState, operands: &str) -> Result<Vec<Move>, &'static str> {
+    operands
+        .split_whitespace()
+        .map(|san| san_to_move(board, san))
+        .collect()
+}
+
+/*
+    Search every position of an EPD test suite with the given limits, printing whether
+    each one was solved followed by the totals
+
+    Returns the number of positions solved and the number of positions searched
+*/
+pub fn run_epd_suite(epd: &str, limits: SearchLimits) -> (usize, usize) {
+    let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+    let transposition_table = TranspositionTable::new(DEFAULT_HASH_SIZE_MB);
+    let mut solved = 0;
+    let mut searched = 0;
+    let mut unreadable = 0;
+
+    for (i, line) in epd.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let position = match EpdPosition::from_epd(line) {
+            Ok(position) => position,
+            Err(err) => {
+                println!("Line {}: {}", i + 1, err);
+                unreadable += 1;
+                continue;
+            }
+        };
+        let id = position
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("line {}", i + 1));
+
+        // every position is independent of the last
+        transposition_table.clear();
+        let result = match search_position(&position.board, &limits, &transposition_table) {
+            Some(result) => result,
+            None => {
+                println!("{}: no legal moves", id);
+                unreadable += 1;
+                continue;
+            }
+        };
+
+        searched += 1;
+        let passed = position.is_solved_by(result.best_move);
+        if passed {
+            solved += 1;
+        }
+        let mut expected = Vec::new();
+        for (opcode, moves) in [("bm", &position.best_moves), ("am", &position.avoid_moves)] {
+            if !moves.is_empty() {
+                let sans: Vec<String> = moves
+                    .iter()
+                    .map(|&mov| move_to_san(&position.board, mov, &zobrist_hasher))
+                    .collect();
+                expected.push(format!("{} {}", opcode, sans.join(" ")));
+            }
+        }
+        println!(
+            "{}: {} {} ({}) depth {} score {}",
+            id,
+            if passed { "pass" } else { "FAIL" },
+            move_to_san(&position.board, result.best_move, &zobrist_hasher),
+            expected.join("; "),
+            result.depth,
+            result.score
+        );
+    }
+
+    println!(
+        "Solved {} of {} positions ({:.1}%)",
+        solved,
+        searched,
+        100.0 * solved as f64 / searched.max(1) as f64
+    );
+    if unreadable > 0 {
+        println!(
+            "Skipped {} positions that could not be searched",
+            unreadable
+        );
+    }
+    (solved, searched)
+}
+
+// the last result sent by the search is the best move found
+fn search_position(
+    board: &BoardState,
+    limits: &SearchLimits,
+    transposition_table: &TranspositionTable,
+) -> Option<SearchResult> {
+    let mut draw_table = DrawTable::new();
+    draw_table.add_board_to_draw_table(board);
+    let (tx, rx) = mpsc::channel();
+    get_best_move(
+        board,
+        &mut draw_table,
+        Instant::now(),
+        limits,
+        DEFAULT_THREADS,
+        &Arc::new(SharedSearch::default()),
+        transposition_table,
+        // the positions of a suite are unrelated, so nothing is learned from the last one
+        &mut Vec::new(),
+        &tx,
+    );
+    rx.try_iter().last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::*;
+
+    #[test]
+    fn epd_parsed() {
+        let position = EpdPosition::from_epd(
+            "r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - bm Bc5; id \"sample; 002\"; c0 \"a comment\";",
+        )
+        .unwrap();
+        assert_eq!(position.id.as_deref(), Some("sample; 002"));
+        assert_eq!(position.best_moves.len(), 1);
+        assert_eq!(position.best_moves[0].to_string(), "b6c5");
+        assert!(position.avoid_moves.is_empty());
+        assert_eq!(
+            position.board.to_fen(),
+            "r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - 0 1"
+        );
+
+        let position = EpdPosition::from_epd(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am f3 g4; bm e4 d4; hmvc 3; fmvn 12;",
+        )
+        .unwrap();
+        assert_eq!(position.avoid_moves.len(), 2);
+        assert_eq!(position.best_moves.len(), 2);
+        assert_eq!(position.board.half_move_clock, 3);
+        assert_eq!(position.board.full_move_number, 12);
+    }
+
+    #[test]
+    fn epd_with_extra_whitespace_parsed() {
+        let position = EpdPosition::from_epd(
+            "  r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1\tw  kq   -\tbm\tBc5;  id \"sample.002\";",
+        )
+        .unwrap();
+        assert_eq!(position.id.as_deref(), Some("sample.002"));
+        assert_eq!(position.best_moves[0].to_string(), "b6c5");
+        assert_eq!(
+            position.board.to_fen(),
+            "r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2Q1RK1 w kq - 0 1"
+        );
+        // the position fields can be followed by nothing at all
+        assert!(EpdPosition::from_epd("8/8/4k3/8/8/3K4/8/8 w - -").is_ok());
+    }
+
+    #[test]
+    fn invalid_epd_rejected() {
+        assert!(EpdPosition::from_epd("8/8/8/8 w").is_err());
+        assert!(EpdPosition::from_epd("8/8/4k3/8/8/3K4/8/8 w - - bm Qh5;").is_err());
+        assert!(EpdPosition::from_epd("8/8/4k3/8/8/3K4/8/8 w - - hmvc x;").is_err());
+    }
+
+    #[test]
+    fn positions_scored() {
+        let position = EpdPosition::from_epd(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am f3 g4; bm e4 d4;",
+        )
+        .unwrap();
+        let moves = generate_moves(&position.board, MoveGenerationMode::AllMoves);
+        let find = |uci: &str| *moves.iter().find(|mov| mov.to_string() == uci).unwrap();
+        assert!(position.is_solved_by(find("e2e4")));
+        assert!(!position.is_solved_by(find("c2c4")));
+        assert!(!position.is_solved_by(find("f2f3")));
+
+        let position =
+            EpdPosition::from_epd("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - am f3;")
+                .unwrap();
+        assert!(position.is_solved_by(find("c2c4")));
+        assert!(!position.is_solved_by(find("f2f3")));
+    }
+
+    #[test]
+    fn suite_run_with_depth_limit() {
+        let epd = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate in one\";\n\
+                   # a comment line\n\
+                   \n\
+                   4k3/8/8/8/8/8/4q3/4K3 w - - bm Kxe2; id \"recapture\";\n\
+                   r3k3/8/8/8/8/8/8/4K3 w - - bm Kd3; id \"illegal best move\";\n";
+        let limits = SearchLimits {
+            depth: 3,
+            ..SearchLimits::default()
+        };
+        assert_eq!(run_epd_suite(epd, limits), (2, 2));
+    }
+
+    #[test]
+    fn suite_run_with_node_limit() {
+        let limits = SearchLimits {
+            depth: 20,
+            nodes: 2000,
+            ..SearchLimits::default()
+        };
+        let epd = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#;";
+        assert_eq!(run_epd_suite(epd, limits), (1, 1));
+    }
+}
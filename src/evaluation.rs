@@ -1,5 +1,6 @@
 pub use crate::board::*;
 pub use crate::board::{PieceColor::*, PieceKind::*};
+use crate::material::{MaterialSignature, SCALE_FACTOR_NORMAL};
 
 /*
     Evaluation function based on https://www.chessprogramming.org/PeSTO%27s_Evaluation_Function
@@ -213,10 +214,12 @@ pub fn get_evaluation(board: &BoardState) -> i32 {
     let mut white_eg = 0;
     let mut black_eg = 0;
     let mut game_phase = 0;
+    let mut material = MaterialSignature::default();
 
     for row in BOARD_START..BOARD_END {
         for col in BOARD_START..BOARD_END {
-            if let Square::Full(Piece { color, kind }) = board.board[row][col] {
+            if let Square::Full(piece @ Piece { color, kind }) = board.board[row][col] {
+                material.add_piece(piece, Point(row, col));
                 game_phase += game_phase_val(kind);
                 if color == White {
                     white_mg +=
@@ -248,7 +251,15 @@ pub fn get_evaluation(board: &BoardState) -> i32 {
         mg_phase = 24;
     }
     let eg_phase = 24 - mg_phase;
-    (mg_score * mg_phase + eg_score * eg_phase) / 24
+    let score = (mg_score * mg_phase + eg_score * eg_phase) / 24;
+
+    // pull the score towards a draw when the side that is ahead can't realistically win
+    let strong_side = if score > 0 {
+        board.to_move
+    } else {
+        board.to_move.opposite()
+    };
+    score * material.scale_factor(strong_side) / SCALE_FACTOR_NORMAL
 }
 
 #[cfg(test)]
@@ -260,4 +271,26 @@ mod tests {
             .unwrap();
         assert_eq!(get_evaluation(&b), 0);
     }
+
+    #[test]
+    fn dead_draws_evaluated_as_draws() {
+        for fen in [
+            "8/8/4k3/8/8/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/6n1 b - - 0 1",
+            "8/8/4k3/8/8/3K4/8/1N3N2 w - - 0 1",
+        ] {
+            let b = BoardState::from_fen(fen).unwrap();
+            assert_eq!(get_evaluation(&b), 0);
+        }
+    }
+
+    #[test]
+    fn drawish_endings_evaluated_closer_to_equal() {
+        // KRvKB is a draw with correct defence, while KRvK is a simple win
+        let drawish = BoardState::from_fen("8/8/4k3/2b5/8/3K4/8/7R w - - 0 1").unwrap();
+        let winning = BoardState::from_fen("8/8/4k3/8/8/3K4/8/7R w - - 0 1").unwrap();
+        assert!(get_evaluation(&drawish) > 0);
+        assert!(get_evaluation(&drawish) < 100);
+        assert!(get_evaluation(&winning) > 400);
+    }
 }
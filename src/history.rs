@@ -0,0 +1,229 @@
+pub use crate::board::*;
+use crate::search::MAX_DEPTH;
+
+// history scores stay between -MAX_HISTORY and MAX_HISTORY
+pub const MAX_HISTORY: i32 = 16384;
+const MAX_HISTORY_BONUS: i32 = 1200;
+// every piece of both colors on every square
+const PIECE_SQUARES: usize = 2 * 6 * 64;
+
+/*
+    Remember which quiet moves have caused beta cutoffs so they can be tried earlier
+    the next time, see https://www.chessprogramming.org/History_Heuristic
+
+    - The butterfly table is indexed by the side to move and the from and to squares
+    - The countermove table holds the move that last refuted the previous move
+    - Continuation history is indexed by the piece and square of a move, along with the
+      piece and square of the move one or two plies before it
+
+    Every table is updated with a gravity formula, the closer an entry gets to MAX_HISTORY
+    the less a bonus changes it, so old results fade as new ones come in
+*/
+#[derive(Clone)]
+pub struct History {
+    butterfly: Box<[[[i32; 64]; 64]; 2]>,
+    counter_moves: Box<[[Option<Move>; 64]; 64]>,
+    continuation: Vec<i32>,
+    // the move made at each ply along with the piece that made it, None for a null move
+    move_stack: [Option<(Move, usize)>; MAX_DEPTH as usize],
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            butterfly: Box::new([[[0; 64]; 64]; 2]),
+            counter_moves: Box::new([[None; 64]; 64]),
+            continuation: vec![0; PIECE_SQUARES * PIECE_SQUARES],
+            move_stack: [None; MAX_DEPTH as usize],
+        }
+    }
+}
+
+impl History {
+    // remember the move about to be made at this ply, so the plies after it can look it up
+    pub fn push_move(&mut self, board: &BoardState, ply_from_root: i32, mov: Move) {
+        self.move_stack[ply_from_root as usize] = Some((mov, piece_square(board, mov)));
+    }
+
+    pub fn push_null_move(&mut self, ply_from_root: i32) {
+        self.move_stack[ply_from_root as usize] = None;
+    }
+
+    // the move that refuted the move played just before this ply the last time it was seen
+    pub fn counter_move(&self, ply_from_root: i32) -> Option<Move> {
+        let (previous, _) = self.previous_move(ply_from_root, 1)?;
+        self.counter_moves[previous.start().square_index()][previous.end().square_index()]
+    }
+
+    // how well this quiet move has done before, higher is better
+    pub fn quiet_score(&self, board: &BoardState, ply_from_root: i32, mov: Move) -> i32 {
+        let mut score = self.butterfly[board.to_move as usize][mov.start().square_index()]
+            [mov.end().square_index()];
+        let current = piece_square(board, mov);
+        for plies_back in 1..=2 {
+            if let Some((_, previous)) = self.previous_move(ply_from_root, plies_back) {
+                score += self.continuation[previous * PIECE_SQUARES + current];
+            }
+        }
+        score
+    }
+
+    /*
+        The quiet move best caused a beta cutoff, it gets a bonus while the quiet moves
+        searched before it that didn't get a cutoff get an equal malus
+    */
+    pub fn update(
+        &mut self,
+        board: &BoardState,
+        ply_from_root: i32,
+        depth: u8,
+        best: Move,
+        quiets_searched: &[Move],
+    ) {
+        let bonus = (depth as i32 * depth as i32 * 16).min(MAX_HISTORY_BONUS);
+        self.update_move(board, ply_from_root, best, bonus);
+        for &mov in quiets_searched {
+            self.update_move(board, ply_from_root, mov, -bonus);
+        }
+
+        if let Some((previous, _)) = self.previous_move(ply_from_root, 1) {
+            self.counter_moves[previous.start().square_index()][previous.end().square_index()] =
+                Some(best);
+        }
+    }
+
+    fn update_move(&mut self, board: &BoardState, ply_from_root: i32, mov: Move, bonus: i32) {
+        let color = board.to_move as usize;
+        apply_bonus(
+            &mut self.butterfly[color][mov.start().square_index()][mov.end().square_index()],
+            bonus,
+        );
+        let current = piece_square(board, mov);
+        for plies_back in 1..=2 {
+            if let Some((_, previous)) = self.previous_move(ply_from_root, plies_back) {
+                apply_bonus(
+                    &mut self.continuation[previous * PIECE_SQUARES + current],
+                    bonus,
+                );
+            }
+        }
+    }
+
+    /*
+        Halve every score before a new search, so what was learned searching the last
+        position still guides the ordering but gives way to what this one finds
+    */
+    pub fn age(&mut self) {
+        for entry in self.butterfly.iter_mut().flatten().flatten() {
+            *entry /= 2;
+        }
+        for entry in self.continuation.iter_mut() {
+            *entry /= 2;
+        }
+    }
+
+    fn previous_move(&self, ply_from_root: i32, plies_back: i32) -> Option<(Move, usize)> {
+        if ply_from_root < plies_back {
+            return None;
+        }
+        self.move_stack[(ply_from_root - plies_back) as usize]
+    }
+}
+
+// gravity keeps the entry within MAX_HISTORY, the bonus is scaled down as it approaches the limit
+fn apply_bonus(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+}
+
+// index of the moving piece and the square it moves to
+fn piece_square(board: &BoardState, mov: Move) -> usize {
+    let start = mov.start();
+    let piece = match board.board[start.0][start.1] {
+        Square::Full(piece) => piece.color as usize * 6 + piece.index(),
+        _ => 0,
+    };
+    piece * 64 + mov.end().square_index()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::*;
+
+    fn find_move(board: &BoardState, uci: &str) -> Move {
+        generate_moves(board, MoveGenerationMode::AllMoves)
+            .into_iter()
+            .find(|mov| mov.to_string() == uci)
+            .unwrap()
+    }
+
+    #[test]
+    fn cutoff_move_rewarded() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let mut history = History::default();
+        let best = find_move(&board, "g1f3");
+        let tried = [find_move(&board, "a2a3"), find_move(&board, "h2h3")];
+        history.update(&board, 0, 4, best, &tried);
+        assert!(history.quiet_score(&board, 0, best) > 0);
+        assert!(history.quiet_score(&board, 0, tried[0]) < 0);
+        assert_eq!(history.quiet_score(&board, 0, find_move(&board, "e2e4")), 0);
+
+        // the other side's moves are kept apart
+        let black =
+            BoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1")
+                .unwrap();
+        assert_eq!(history.quiet_score(&black, 0, find_move(&black, "g8f6")), 0);
+    }
+
+    #[test]
+    fn history_bounded_by_gravity() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let mut history = History::default();
+        let best = find_move(&board, "g1f3");
+        let worst = find_move(&board, "a2a3");
+        for _ in 0..1000 {
+            history.update(&board, 0, 30, best, &[worst]);
+        }
+        assert!(history.quiet_score(&board, 0, best) <= MAX_HISTORY);
+        assert!(history.quiet_score(&board, 0, best) > MAX_HISTORY / 2);
+        assert!(history.quiet_score(&board, 0, worst) >= -MAX_HISTORY);
+    }
+
+    #[test]
+    fn aging_halves_scores() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let mut history = History::default();
+        let best = find_move(&board, "g1f3");
+        let worst = find_move(&board, "a2a3");
+        history.update(&board, 0, 5, best, &[worst]);
+        let (best_score, worst_score) = (
+            history.quiet_score(&board, 0, best),
+            history.quiet_score(&board, 0, worst),
+        );
+        history.age();
+        assert_eq!(history.quiet_score(&board, 0, best), best_score / 2);
+        assert_eq!(history.quiet_score(&board, 0, worst), worst_score / 2);
+    }
+
+    #[test]
+    fn counter_and_continuation_follow_previous_move() {
+        let zobrist_hasher = crate::zobrist::ZobristHasher::create_zobrist_hasher();
+        let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let mut history = History::default();
+        let first = find_move(&board, "e2e4");
+        history.push_move(&board, 0, first);
+        board.make_move(first, &zobrist_hasher);
+        let reply = find_move(&board, "e7e5");
+        assert_eq!(history.counter_move(1), None);
+        history.update(&board, 1, 3, reply, &[]);
+        assert_eq!(history.counter_move(1), Some(reply));
+        assert!(history.quiet_score(&board, 1, reply) > 0);
+
+        // after a different previous move only the butterfly part of the score is left
+        let butterfly_only = history.quiet_score(&board, 0, reply);
+        assert!(history.quiet_score(&board, 1, reply) > butterfly_only);
+        history.push_null_move(0);
+        assert_eq!(history.counter_move(1), None);
+        assert_eq!(history.quiet_score(&board, 1, reply), butterfly_only);
+    }
+}
@@ -1,13 +1,22 @@
 extern crate clap;
 use clap::{App, Arg};
-use std::{cmp::max, time::Instant};
+use std::{cmp::max, fs, time::Instant};
 mod board;
 mod draw_table;
 mod engine;
+mod epd;
 mod evaluation;
+mod history;
+mod mate_solver;
+mod material;
 mod move_generation;
+mod notation;
+mod perft;
+mod pgn;
 mod search;
+mod see;
 mod time_control;
+mod transposition_table;
 mod uci;
 mod utils;
 mod zobrist;
@@ -58,6 +67,59 @@ fn main() {
                 "Evaluates <FEN STRING> to benchmark move generation - incompatible with play self",
             ),
         )
+        .arg(
+            Arg::with_name("perft")
+                .long("perft")
+                .help("Count the moves from <FEN STRING> to <DEPTH> for each root move, with full statistics for every depth"),
+        )
+        .arg(
+            Arg::with_name("epd")
+                .short("E")
+                .long("epd")
+                .value_name("FILE")
+                .help("Run the EPD test suite in <FILE> and report which positions were solved")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("move time")
+                .long("move-time")
+                .value_name("MS")
+                .help("Time to search each EPD position for, defaults to 1000 unless a depth or node limit is given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("nodes")
+                .long("nodes")
+                .value_name("NODES")
+                .help("Number of nodes to search each EPD position for, or to add to the proof tree of a mate search")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mate")
+                .long("mate")
+                .value_name("MOVES")
+                .help("Prove whether <FEN STRING> has a forced mate in at most <MOVES> moves and print the mating line")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("pgn")
+                .long("pgn")
+                .value_name("FILE")
+                .help("Write the self play game to <FILE> in PGN format, otherwise it is printed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("read pgn")
+                .long("read-pgn")
+                .value_name("FILE")
+                .help("Replay every game in the PGN <FILE> and print a summary of each one")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("eval comments")
+                .long("eval-comments")
+                .help("Add the score and depth of each self play move to the PGN as comments"),
+        )
         .arg(
             Arg::with_name("simple print")
                 .short("S")
@@ -81,7 +143,7 @@ fn main() {
     }
 
     let fen = matches.value_of("fen").unwrap_or(board::DEFAULT_FEN_STRING);
-    let board = match board::BoardState::from_fen(fen) {
+    let mut board = match board::BoardState::from_fen(fen) {
         Ok(b) => b,
         Err(err) => {
             println!("{}", err);
@@ -94,7 +156,7 @@ fn main() {
         let start = Instant::now();
         let zobrist_hasher = zobrist::ZobristHasher::create_zobrist_hasher();
         move_generation::generate_moves_test(
-            &board,
+            &mut board,
             0,
             depth as usize,
             &mut moves_states,
@@ -113,11 +175,155 @@ fn main() {
         return;
     }
 
+    if matches.is_present("perft") {
+        let zobrist_hasher = zobrist::ZobristHasher::create_zobrist_hasher();
+        for line in perft::perft_report(&board, depth, &zobrist_hasher) {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    if let Some(moves) = matches.value_of("mate") {
+        let max_moves = match moves.parse::<u8>() {
+            Ok(moves) if moves > 0 => moves,
+            _ => {
+                println!("Invalid number of moves provided");
+                return;
+            }
+        };
+        let mut limits = search::SearchLimits::default();
+        if let Some(nodes) = matches.value_of("nodes") {
+            match nodes.parse() {
+                Ok(nodes) => limits.nodes = nodes,
+                Err(_) => {
+                    println!("Invalid node count provided");
+                    return;
+                }
+            }
+        }
+        let start = Instant::now();
+        let shared = search::SharedSearch::default();
+        let mut solver = mate_solver::MateSolver::new(
+            &board,
+            &limits,
+            transposition_table::DEFAULT_HASH_SIZE_MB,
+            start,
+            &shared,
+        );
+        match solver.solve(&board, max_moves) {
+            mate_solver::MateResult::Mate(line) => {
+                let zobrist_hasher = zobrist::ZobristHasher::create_zobrist_hasher();
+                let mut san = Vec::new();
+                for mov in line.iter() {
+                    san.push(notation::move_to_san(&board, *mov, &zobrist_hasher));
+                    board.make_move(*mov, &zobrist_hasher);
+                }
+                println!("Mate in {}: {}", line.len().div_ceil(2), san.join(" "));
+            }
+            mate_solver::MateResult::NoMate => println!("No mate in {}", max_moves),
+            mate_solver::MateResult::Unknown => {
+                println!("Could not decide if there is a mate in {}", max_moves)
+            }
+        }
+        println!(
+            "Searched {} nodes in {:?}",
+            solver.nodes_searched(),
+            Instant::now().duration_since(start)
+        );
+        return;
+    }
+
+    if let Some(path) = matches.value_of("epd") {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                println!("Could not read EPD from {}: {}", path, err);
+                return;
+            }
+        };
+        let mut limits = search::SearchLimits::default();
+        if matches.is_present("depth") {
+            limits.depth = depth;
+        }
+        if let Some(nodes) = matches.value_of("nodes") {
+            match nodes.parse() {
+                Ok(nodes) => limits.nodes = nodes,
+                Err(_) => {
+                    println!("Invalid node count provided");
+                    return;
+                }
+            }
+        }
+        const DEFAULT_MOVE_TIME_MS: &str = "1000";
+        let move_time = match matches.value_of("move time") {
+            Some(move_time) => Some(move_time),
+            None if !matches.is_present("depth") && !matches.is_present("nodes") => {
+                Some(DEFAULT_MOVE_TIME_MS)
+            }
+            None => None,
+        };
+        if let Some(move_time) = move_time {
+            match move_time.parse() {
+                Ok(ms) => limits.movetime = Some(ms),
+                Err(_) => {
+                    println!("Invalid move time provided");
+                    return;
+                }
+            }
+        }
+        epd::run_epd_suite(&text, limits);
+        return;
+    }
+
+    if let Some(path) = matches.value_of("read pgn") {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                println!("Could not read PGN from {}: {}", path, err);
+                return;
+            }
+        };
+        let zobrist_hasher = zobrist::ZobristHasher::create_zobrist_hasher();
+        for (i, game) in pgn::read_pgn(&text, true).iter().enumerate() {
+            match game {
+                Ok(game) => {
+                    let final_board = game.positions(&zobrist_hasher).pop().unwrap();
+                    println!(
+                        "Game {}: {} vs {}, {} plies, {} - {}",
+                        i + 1,
+                        game.tag("White").unwrap_or("?"),
+                        game.tag("Black").unwrap_or("?"),
+                        game.moves.len(),
+                        game.result,
+                        final_board.to_fen()
+                    );
+                }
+                Err(err) => println!("{}", err),
+            }
+        }
+        return;
+    }
+
     if matches.is_present("play self") {
         let simple_print = matches.is_present("simple print");
         let max_moves = 100;
         let time_per_move_ms = 1000;
-        engine::play_game_against_self(&board, max_moves, time_per_move_ms, simple_print);
+        let eval_comments = matches.is_present("eval comments");
+        let game = engine::play_game_against_self(
+            &board,
+            max_moves,
+            time_per_move_ms,
+            simple_print,
+            eval_comments,
+        );
+        match matches.value_of("pgn") {
+            Some(path) => {
+                if let Err(err) = fs::write(path, game.to_pgn()) {
+                    println!("Could not write PGN to {}: {}", path, err);
+                }
+            }
+            None => print!("\n{}", game.to_pgn()),
+        }
         return;
     }
 
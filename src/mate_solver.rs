@@ -0,0 +1,471 @@
+pub use crate::move_generation::*;
+use crate::search::{SearchLimits, SharedSearch};
+use crate::utils::out_of_time;
+use crate::zobrist::ZobristHasher;
+use std::time::Instant;
+
+// proof and disproof numbers are capped here, a node with a number this high can't be proven
+const INFINITY: u32 = u32::MAX / 2;
+// more than the legal moves in any position, so expanding a node never adds more children
+const MAX_CHILDREN: usize = 256;
+
+/*
+    The outcome of looking for a forced mate, the line of a mate alternates the moves of
+    the side giving mate and the side being mated, ending with the mating move
+*/
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MateResult {
+    Mate(Vec<Move>),
+    // there is no forced mate in the number of moves searched
+    NoMate,
+    // the search ran out of nodes or time, or was stopped, before it could decide
+    Unknown,
+}
+
+/*
+    A position in the proof tree. At an OR node the side giving mate is to move and
+    only one child needs to be proven, at an AND node the side being mated is to move
+    and every child needs to be proven
+
+    Children are always added together, so they are stored next to each other
+*/
+struct Node {
+    mov: Option<Move>,
+    parent: usize,
+    first_child: usize,
+    child_count: usize,
+    proof: u32,
+    disproof: u32,
+    // the moves the side giving mate has left, including the one about to be played
+    moves_left: u8,
+    is_or_node: bool,
+    expanded: bool,
+}
+
+impl Node {
+    fn new(mov: Option<Move>, parent: usize, moves_left: u8, is_or_node: bool) -> Node {
+        Node {
+            mov,
+            parent,
+            first_child: 0,
+            child_count: 0,
+            proof: 1,
+            disproof: 1,
+            moves_left,
+            is_or_node,
+            expanded: false,
+        }
+    }
+
+    fn is_proven(&self) -> bool {
+        self.proof == 0
+    }
+
+    fn is_solved(&self) -> bool {
+        self.proof == 0 || self.disproof == 0
+    }
+
+    fn children(&self) -> std::ops::Range<usize> {
+        self.first_child..self.first_child + self.child_count
+    }
+}
+
+/*
+    Find forced mates with proof-number search, see https://www.chessprogramming.org/Proof-Number_Search
+
+    Instead of a score every node holds how many more leaves would need to be proven
+    to show there is a mate, and how many to show there isn't. The search always expands
+    the leaf that does the most for both, which quickly finds narrow forcing lines
+    that an alpha beta search with an evaluation would have to search to the full depth
+
+    The repetition and fifty move rules are ignored, a mate within a few moves is
+    never long enough for them to matter
+
+    There is no transposition table, so the tree is kept within a memory budget and
+    the search gives up once it is full
+*/
+pub struct MateSolver<'a> {
+    tree: Vec<Node>,
+    // the most nodes the tree can hold within the memory budget
+    tree_limit: usize,
+    zobrist_hasher: ZobristHasher,
+    start: Instant,
+    time_to_move_ms: u128,
+    node_limit: u64,
+    search_moves: Vec<Move>,
+    shared: &'a SharedSearch,
+    nodes_searched: u64,
+}
+
+impl<'a> MateSolver<'a> {
+    /*
+        The node and time limits and the root moves to search are taken from the search
+        limits, the node limit counts the nodes added over every search for a shorter mate
+
+        The tree never takes more than hash_size_mb megabytes, the same as the Hash option
+    */
+    pub fn new(
+        board: &BoardState,
+        limits: &SearchLimits,
+        hash_size_mb: usize,
+        start: Instant,
+        shared: &'a SharedSearch,
+    ) -> MateSolver<'a> {
+        MateSolver {
+            tree: Vec::new(),
+            tree_limit: hash_size_mb * 1024 * 1024 / std::mem::size_of::<Node>(),
+            zobrist_hasher: ZobristHasher::create_zobrist_hasher(),
+            start,
+            time_to_move_ms: limits.time_to_move_ms(board.to_move),
+            node_limit: limits.nodes,
+            search_moves: limits.search_moves.clone(),
+            shared,
+            nodes_searched: 0,
+        }
+    }
+
+    // nodes added to the proof trees of every search so far
+    pub fn nodes_searched(&self) -> u64 {
+        self.nodes_searched
+    }
+
+    /*
+        Find the shortest forced mate for the side to move in at most max_moves moves
+
+        A mate in one is looked for first, then a mate in two and so on, so a mate is
+        only returned once every shorter mate has been disproven
+    */
+    pub fn solve(&mut self, board: &BoardState, max_moves: u8) -> MateResult {
+        for moves in 1..=max_moves {
+            match self.prove(board, moves) {
+                Some(true) => return MateResult::Mate(self.mating_line()),
+                Some(false) => continue,
+                None => return MateResult::Unknown,
+            }
+        }
+        MateResult::NoMate
+    }
+
+    // whether there is a mate in this many moves, None if the search had to give up
+    fn prove(&mut self, board: &BoardState, moves: u8) -> Option<bool> {
+        self.tree.clear();
+        self.tree.push(Node::new(None, 0, moves, true));
+
+        while !self.tree[0].is_solved() {
+            if self.should_stop() {
+                return None;
+            }
+            let (leaf, mut leaf_board) = self.most_proving_node(board);
+            self.expand(leaf, &mut leaf_board);
+            self.update_ancestors(leaf);
+        }
+        Some(self.tree[0].is_proven())
+    }
+
+    // checked before every expansion, so the children always fit within the tree limit
+    fn should_stop(&self) -> bool {
+        self.nodes_searched >= self.node_limit
+            || self.tree.len() + MAX_CHILDREN > self.tree_limit
+            || self.shared.is_aborted()
+            || (!self.shared.is_pondering() && out_of_time(self.start, self.time_to_move_ms))
+    }
+
+    /*
+        Follow the child that is cheapest to prove from OR nodes and cheapest to disprove
+        from AND nodes until reaching a leaf, along with the board at that leaf
+    */
+    fn most_proving_node(&self, board: &BoardState) -> (usize, BoardState) {
+        let mut board = board.clone();
+        let mut index = 0;
+        while self.tree[index].expanded {
+            let node = &self.tree[index];
+            index = node
+                .children()
+                .min_by_key(|&child| {
+                    if node.is_or_node {
+                        self.tree[child].proof
+                    } else {
+                        self.tree[child].disproof
+                    }
+                })
+                .unwrap();
+            board.make_move(self.tree[index].mov.unwrap(), &self.zobrist_hasher);
+        }
+        (index, board)
+    }
+
+    /*
+        Add every legal move from the leaf to the tree. Each child is checked for mate
+        and stalemate straight away, the rest start with the number of replies as the
+        proof or disproof number, so positions with fewer replies are looked at first
+    */
+    fn expand(&mut self, index: usize, board: &mut BoardState) {
+        let mut moves = generate_moves(board, MoveGenerationMode::AllMoves);
+        if index == 0 && !self.search_moves.is_empty() {
+            moves.retain(|mov| self.search_moves.contains(mov));
+        }
+        let node = &self.tree[index];
+        let (moves_left, is_or_node) = (node.moves_left, node.is_or_node);
+        let first_child = self.tree.len();
+        // grow by doubling as a Vec would, but never allocate past the tree limit
+        if first_child + moves.len() > self.tree.capacity() {
+            let additional = self
+                .tree
+                .capacity()
+                .max(moves.len())
+                .min(self.tree_limit - first_child);
+            self.tree.reserve_exact(additional);
+        }
+
+        for mov in moves {
+            let undo = board.make_move(mov, &self.zobrist_hasher);
+            let mut child = if is_or_node {
+                Node::new(Some(mov), index, moves_left - 1, false)
+            } else {
+                Node::new(Some(mov), index, moves_left, true)
+            };
+            let in_check = is_check(board, board.to_move);
+            if is_or_node && !in_check && child.moves_left == 0 {
+                // the last move has to give check to be mate
+                (child.proof, child.disproof) = (INFINITY, 0);
+            } else {
+                let replies = generate_moves(board, MoveGenerationMode::AllMoves).len() as u32;
+                (child.proof, child.disproof) = match (replies, is_or_node) {
+                    // mate is good for the side giving it, stalemate never is
+                    (0, true) if in_check => (0, INFINITY),
+                    (0, _) => (INFINITY, 0),
+                    (_, true) if child.moves_left == 0 => (INFINITY, 0),
+                    (replies, true) => (replies, 1),
+                    (replies, false) => (1, replies),
+                };
+            }
+            board.unmake_move(mov, &undo);
+            self.tree.push(child);
+            self.nodes_searched += 1;
+        }
+
+        let child_count = self.tree.len() - first_child;
+        let node = &mut self.tree[index];
+        node.first_child = first_child;
+        node.child_count = child_count;
+        node.expanded = true;
+    }
+
+    /*
+        An OR node needs one child proven and every child disproven, an AND node is
+        the other way around. Changes are passed up until they reach the root
+    */
+    fn update_ancestors(&mut self, mut index: usize) {
+        loop {
+            let node = &self.tree[index];
+            let children = node.children().map(|child| &self.tree[child]);
+            let (proof, disproof) = if node.is_or_node {
+                (
+                    children.clone().map(|child| child.proof).min(),
+                    children.map(|child| child.disproof).reduce(add_capped),
+                )
+            } else {
+                (
+                    children.clone().map(|child| child.proof).reduce(add_capped),
+                    children.map(|child| child.disproof).min(),
+                )
+            };
+            // a node without children has no moves, which is never good for the side to move
+            let node = &mut self.tree[index];
+            if node.is_or_node {
+                node.proof = proof.unwrap_or(INFINITY);
+                node.disproof = disproof.unwrap_or(0);
+            } else {
+                node.proof = proof.unwrap_or(0);
+                node.disproof = disproof.unwrap_or(INFINITY);
+            }
+            if index == 0 {
+                break;
+            }
+            index = node.parent;
+        }
+    }
+
+    // how many plies it takes to mate from a proven node, assuming the longest defence
+    fn plies_to_mate(&self, index: usize) -> u32 {
+        let node = &self.tree[index];
+        if !node.expanded {
+            // only a mate is proven without being expanded
+            return 0;
+        }
+        let children = node
+            .children()
+            .filter(|&child| self.tree[child].is_proven())
+            .map(|child| self.plies_to_mate(child));
+        1 + if node.is_or_node {
+            children.min().unwrap_or(0)
+        } else {
+            children.max().unwrap_or(0)
+        }
+    }
+
+    /*
+        The line from the root of a proven tree, taking the quickest mate for the side
+        giving mate and the longest defence for the side being mated
+    */
+    fn mating_line(&self) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut index = 0;
+        while self.tree[index].expanded {
+            let node = &self.tree[index];
+            let proven = node
+                .children()
+                .filter(|&child| self.tree[child].is_proven());
+            index = if node.is_or_node {
+                proven.min_by_key(|&child| self.plies_to_mate(child))
+            } else {
+                proven.max_by_key(|&child| self.plies_to_mate(child))
+            }
+            .unwrap();
+            line.push(self.tree[index].mov.unwrap());
+        }
+        line
+    }
+}
+
+fn add_capped(a: u32, b: u32) -> u32 {
+    a.saturating_add(b).min(INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transposition_table::DEFAULT_HASH_SIZE_MB;
+
+    fn solve(fen: &str, max_moves: u8) -> MateResult {
+        let board = BoardState::from_fen(fen).unwrap();
+        let shared = SharedSearch::default();
+        MateSolver::new(
+            &board,
+            &SearchLimits::default(),
+            DEFAULT_HASH_SIZE_MB,
+            Instant::now(),
+            &shared,
+        )
+        .solve(&board, max_moves)
+    }
+
+    fn line(result: MateResult) -> Vec<String> {
+        match result {
+            MateResult::Mate(line) => line.iter().map(|mov| mov.to_string()).collect(),
+            result => panic!("expected a mate, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn mate_in_one() {
+        assert_eq!(
+            line(solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3)),
+            ["a1a8"]
+        );
+        // the side giving mate can be black
+        assert_eq!(
+            line(solve("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 3)),
+            ["a8a1"]
+        );
+    }
+
+    #[test]
+    fn mate_in_two_with_longest_defence() {
+        assert_eq!(
+            line(solve("2r3k1/5ppp/8/8/8/8/3R4/3R2K1 w - - 0 1", 4)),
+            ["d2d8", "c8d8", "d1d8"]
+        );
+    }
+
+    #[test]
+    fn shorter_mates_disproven() {
+        let fen = "2r3k1/5ppp/8/8/8/8/3R4/3R2K1 w - - 0 1";
+        assert_eq!(solve(fen, 1), MateResult::NoMate);
+        // asking for a longer mate still finds the shortest one
+        assert_eq!(line(solve(fen, 6)).len(), 3);
+    }
+
+    #[test]
+    fn mate_in_three() {
+        // 1. Ra6 f6 2. Bxf6+ Rg7 3. Rxa8#
+        let fen = "r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1";
+        let line = line(solve(fen, 3));
+        assert_eq!(line.len(), 5);
+        assert_eq!(solve(fen, 2), MateResult::NoMate);
+    }
+
+    #[test]
+    fn stalemate_is_not_mate() {
+        // every move either stalemates the king or lets it take the pawn
+        assert_eq!(
+            solve("k7/P7/1K6/8/8/8/8/8 w - - 0 1", 3),
+            MateResult::NoMate
+        );
+        assert_eq!(
+            solve("k7/8/1QK5/8/8/8/8/8 w - - 0 1", 1),
+            MateResult::Mate(line_of("k7/8/1QK5/8/8/8/8/8 w - - 0 1", &["b6b7"]))
+        );
+    }
+
+    fn line_of(fen: &str, uci: &[&str]) -> Vec<Move> {
+        let board = BoardState::from_fen(fen).unwrap();
+        let moves = generate_moves(&board, MoveGenerationMode::AllMoves);
+        uci.iter()
+            .map(|&mov| *moves.iter().find(|m| m.to_string() == mov).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn node_limit_gives_up() {
+        let board = BoardState::from_fen("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1").unwrap();
+        let shared = SharedSearch::default();
+        let limits = SearchLimits {
+            nodes: 50,
+            ..SearchLimits::default()
+        };
+        let mut solver = MateSolver::new(
+            &board,
+            &limits,
+            DEFAULT_HASH_SIZE_MB,
+            Instant::now(),
+            &shared,
+        );
+        assert_eq!(solver.solve(&board, 3), MateResult::Unknown);
+        assert!(solver.nodes_searched() > 0);
+    }
+
+    #[test]
+    fn memory_budget_gives_up() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let shared = SharedSearch::default();
+        let mut solver =
+            MateSolver::new(&board, &SearchLimits::default(), 1, Instant::now(), &shared);
+        assert_eq!(solver.solve(&board, 4), MateResult::Unknown);
+        assert!(solver.tree.capacity() <= solver.tree_limit);
+        assert!(solver.tree.capacity() * std::mem::size_of::<Node>() <= 1024 * 1024);
+
+        // without any memory the search can't start
+        let mut solver =
+            MateSolver::new(&board, &SearchLimits::default(), 0, Instant::now(), &shared);
+        assert_eq!(solver.solve(&board, 1), MateResult::Unknown);
+    }
+
+    #[test]
+    fn root_moves_restricted() {
+        let board = BoardState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let shared = SharedSearch::default();
+        let limits = SearchLimits {
+            search_moves: line_of("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &["a1a2"]),
+            ..SearchLimits::default()
+        };
+        let mut solver = MateSolver::new(
+            &board,
+            &limits,
+            DEFAULT_HASH_SIZE_MB,
+            Instant::now(),
+            &shared,
+        );
+        assert_eq!(solver.solve(&board, 1), MateResult::NoMate);
+    }
+}
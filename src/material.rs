@@ -0,0 +1,233 @@
+pub use crate::board::*;
+pub use crate::board::{PieceColor::*, PieceKind::*};
+
+// scale factors are out of this value, a scale of SCALE_FACTOR_NORMAL leaves the evaluation untouched
+pub const SCALE_FACTOR_NORMAL: i32 = 16;
+// used for endings where the extra material is very rarely enough to win
+const SCALE_FACTOR_DRAWISH: i32 = 2;
+
+/*
+    Count of each kind of piece for both sides, used to recognise material
+    combinations that can't be won, or are very unlikely to be won
+    See https://www.chessprogramming.org/Material#Insufficient_Material
+*/
+#[derive(Default)]
+pub struct MaterialSignature {
+    counts: [[u8; 6]; 2],
+    light_square_bishops: u8,
+    dark_square_bishops: u8,
+}
+
+impl MaterialSignature {
+    pub fn from_board(board: &BoardState) -> MaterialSignature {
+        let mut material = MaterialSignature::default();
+        for row in BOARD_START..BOARD_END {
+            for col in BOARD_START..BOARD_END {
+                if let Square::Full(piece) = board.board[row][col] {
+                    material.add_piece(piece, Point(row, col));
+                }
+            }
+        }
+        material
+    }
+
+    pub fn add_piece(&mut self, piece: Piece, point: Point) {
+        self.counts[color_index(piece.color)][piece.index()] += 1;
+        if piece.kind == Bishop {
+            if (point.0 + point.1).is_multiple_of(2) {
+                self.light_square_bishops += 1;
+            } else {
+                self.dark_square_bishops += 1;
+            }
+        }
+    }
+
+    pub fn count(&self, color: PieceColor, kind: PieceKind) -> u8 {
+        self.counts[color_index(color)][kind.index()]
+    }
+
+    // anything other than the king and pawns, without it zugzwang is likely
+    pub fn has_non_pawn_material(&self, color: PieceColor) -> bool {
+        [Knight, Bishop, Rook, Queen]
+            .iter()
+            .any(|&kind| self.count(color, kind) > 0)
+    }
+
+    fn total(&self, kind: PieceKind) -> u8 {
+        self.count(White, kind) + self.count(Black, kind)
+    }
+
+    /*
+        Neither side can possibly checkmate, this covers KvK, KNvK and any number of
+        bishops that are all on the same colored squares (KBvK, KBvKB etc)
+    */
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.total(Pawn) > 0 || self.total(Rook) > 0 || self.total(Queen) > 0 {
+            return false;
+        }
+
+        match self.total(Knight) {
+            0 => self.light_square_bishops == 0 || self.dark_square_bishops == 0,
+            1 => self.total(Bishop) == 0,
+            _ => false,
+        }
+    }
+
+    /*
+        How much of the evaluation should be kept when strong_side is ahead, out of
+        SCALE_FACTOR_NORMAL. Only a few pawnless endings are known to be drawish, a lone
+        rook against a lone minor piece (KRvKB, KRvKN) and a lone minor piece against
+        another (KBvKN), while two knights can't force mate against a bare king
+    */
+    pub fn scale_factor(&self, strong_side: PieceColor) -> i32 {
+        if self.is_insufficient_material() {
+            return 0;
+        }
+
+        let weak_side = strong_side.opposite();
+        if self.count(strong_side, Pawn) > 0 {
+            return SCALE_FACTOR_NORMAL;
+        }
+
+        let only_knights =
+            self.non_pawn_material(strong_side) == self.count(strong_side, Knight) as i32 * 3;
+        let bare_king = self.non_pawn_material(weak_side) == 0 && self.count(weak_side, Pawn) == 0;
+        if only_knights && self.count(strong_side, Knight) <= 2 && bare_king {
+            return 0;
+        }
+
+        match (self.lone_piece(strong_side), self.lone_piece(weak_side)) {
+            (Some(Rook | Knight | Bishop), Some(Knight | Bishop)) => SCALE_FACTOR_DRAWISH,
+            _ => SCALE_FACTOR_NORMAL,
+        }
+    }
+
+    // the only piece this side has other than the king and pawns
+    fn lone_piece(&self, color: PieceColor) -> Option<PieceKind> {
+        let mut pieces = [Knight, Bishop, Rook, Queen]
+            .into_iter()
+            .filter(|&kind| self.count(color, kind) > 0);
+        match (pieces.next(), pieces.next()) {
+            (Some(kind), None) if self.count(color, kind) == 1 => Some(kind),
+            _ => None,
+        }
+    }
+
+    // a rough material count in pawns, only used to compare the two sides
+    fn non_pawn_material(&self, color: PieceColor) -> i32 {
+        self.count(color, Knight) as i32 * 3
+            + self.count(color, Bishop) as i32 * 3
+            + self.count(color, Rook) as i32 * 5
+            + self.count(color, Queen) as i32 * 9
+    }
+}
+
+fn color_index(color: PieceColor) -> usize {
+    match color {
+        White => 0,
+        Black => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(fen: &str) -> MaterialSignature {
+        MaterialSignature::from_board(&BoardState::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn insufficient_material_detected() {
+        // KvK
+        assert!(material("8/8/4k3/8/8/3K4/8/8 w - - 0 1").is_insufficient_material());
+        // KBvK
+        assert!(material("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1").is_insufficient_material());
+        // KNvK
+        assert!(material("8/8/4k3/8/8/3K4/8/6n1 w - - 0 1").is_insufficient_material());
+        // KBvKB with bishops on the same colored squares
+        assert!(material("8/8/4k3/1b6/8/3K4/8/5B2 w - - 0 1").is_insufficient_material());
+    }
+
+    #[test]
+    fn sufficient_material_detected() {
+        // KBvKB with bishops on opposite colored squares can still be mated
+        assert!(!material("8/8/4k3/2b5/8/3K4/8/5B2 w - - 0 1").is_insufficient_material());
+        // KNvKN
+        assert!(!material("8/8/4k3/8/8/3K4/8/1n3N2 w - - 0 1").is_insufficient_material());
+        // KNNvK
+        assert!(!material("8/8/4k3/8/8/3K4/8/1N3N2 w - - 0 1").is_insufficient_material());
+        // KPvK
+        assert!(!material("8/8/4k3/8/8/3K4/4P3/8 w - - 0 1").is_insufficient_material());
+        // KRvK
+        assert!(!material("8/8/4k3/8/8/3K4/8/7R w - - 0 1").is_insufficient_material());
+    }
+
+    #[test]
+    fn non_pawn_material_detected() {
+        let pawn_ending = material("7k/5K2/5P1p/3p4/6P1/3p4/8/8 w - - 0 1");
+        assert!(!pawn_ending.has_non_pawn_material(White));
+        assert!(!pawn_ending.has_non_pawn_material(Black));
+        let knight = material("8/8/4k3/8/8/3K4/4P3/6n1 w - - 0 1");
+        assert!(!knight.has_non_pawn_material(White));
+        assert!(knight.has_non_pawn_material(Black));
+    }
+
+    #[test]
+    fn drawish_endings_scaled() {
+        // KNNvK
+        assert_eq!(
+            material("8/8/4k3/8/8/3K4/8/1N3N2 w - - 0 1").scale_factor(White),
+            0
+        );
+        // KRvKB
+        assert_eq!(
+            material("8/8/4k3/2b5/8/3K4/8/7R w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_DRAWISH
+        );
+        // KRvKN
+        assert_eq!(
+            material("8/8/4k3/2n5/8/3K4/8/7R w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_DRAWISH
+        );
+        // KBvKN
+        assert_eq!(
+            material("8/8/4k3/2n5/8/3K4/8/7B w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_DRAWISH
+        );
+        // dead draws are scaled all the way down
+        assert_eq!(
+            material("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1").scale_factor(White),
+            0
+        );
+    }
+
+    #[test]
+    fn winning_endings_not_scaled() {
+        // KQvKR
+        assert_eq!(
+            material("8/8/4k3/2r5/8/3K4/8/7Q w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_NORMAL
+        );
+        // KBBvKN, the bishop pair usually wins against a lone knight
+        assert_eq!(
+            material("8/8/4k3/2n5/8/3K4/8/5BB1 w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_NORMAL
+        );
+        // KBNvK
+        assert_eq!(
+            material("8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_NORMAL
+        );
+        // KRPvKB, the pawn gives winning chances
+        assert_eq!(
+            material("8/8/4k3/2b5/8/3K4/4P3/7R w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_NORMAL
+        );
+        // KNNvKP, the pawn means the defender can't rely on stalemate
+        assert_eq!(
+            material("8/8/4k3/8/4p3/3K4/8/1N3N2 w - - 0 1").scale_factor(White),
+            SCALE_FACTOR_NORMAL
+        );
+    }
+}
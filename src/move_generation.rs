@@ -41,22 +41,17 @@ pub enum MoveGenerationMode {
     CapturesOnly,
 }
 
-const WHITE_KING_SIDE_CASTLE_ALG: Option<(Point, Point)> = Some((Point(9, 6), Point(9, 8)));
-const WHITE_QUEEN_SIDE_CASTLE_ALG: Option<(Point, Point)> = Some((Point(9, 6), Point(9, 4)));
-const BLACK_KING_SIDE_CASTLE_ALG: Option<(Point, Point)> = Some((Point(2, 6), Point(2, 8)));
-const BLACK_QUEEN_SIDE_CASTLE_ALG: Option<(Point, Point)> = Some((Point(2, 6), Point(2, 4)));
+const QUEEN_PROMOTION_SCORE: i32 = 800; // queen value - pawn value
+const UNDER_PROMOTION_SCORE: i32 = -999999999; // under promotions should be tried last
 
 /*
     Generate all possible *legal* moves from the given board
-    Also sets appropriate variables for the board state
 */
-pub fn generate_moves(
-    board: &BoardState,
-    move_gen_mode: MoveGenerationMode,
-    zobrist_hasher: &ZobristHasher,
-) -> Vec<BoardState> {
+pub fn generate_moves(board: &BoardState, move_gen_mode: MoveGenerationMode) -> Vec<Move> {
     //usually there is at minimum 16 moves in a position, so it make sense to preallocate some space to avoid excessive reallocations
-    let mut new_moves: Vec<BoardState> = Vec::with_capacity(16);
+    let mut new_moves: Vec<Move> = Vec::with_capacity(16);
+    // moves are played out on this copy to see if they leave the king in check
+    let mut scratch_board = board.clone();
 
     for i in BOARD_START..BOARD_END {
         for j in BOARD_START..BOARD_END {
@@ -64,11 +59,10 @@ pub fn generate_moves(
                 if piece.color == board.to_move {
                     generate_moves_for_piece(
                         piece,
-                        board,
+                        &mut scratch_board,
                         Point(i, j),
                         &mut new_moves,
                         move_gen_mode,
-                        zobrist_hasher,
                     );
                 }
             }
@@ -76,11 +70,40 @@ pub fn generate_moves(
     }
 
     if move_gen_mode == MoveGenerationMode::AllMoves {
-        generate_castling_moves(board, &mut new_moves, zobrist_hasher);
+        generate_castling_moves(board, &mut new_moves);
     }
     new_moves
 }
 
+/*
+    Value used to order moves before searching them, a higher value means the move will be considered first
+*/
+pub fn order_heuristic(board: &BoardState, mov: Move) -> i32 {
+    if let Some(kind) = mov.promotion_kind() {
+        // promoting to a piece that isn't a queen is rarely a good idea
+        if kind == Queen {
+            QUEEN_PROMOTION_SCORE
+        } else {
+            UNDER_PROMOTION_SCORE
+        }
+    } else if mov.is_capture() {
+        let start = mov.start();
+        let end = mov.end();
+        let victim = match board.board[end.0][end.1] {
+            Square::Full(piece) => piece.index(),
+            _ => Pawn.index(), // en passant
+        };
+        let attacker = match board.board[start.0][start.1] {
+            Square::Full(piece) => piece.index(),
+            _ => Pawn.index(),
+        };
+        MVV_LVA[victim][attacker]
+    } else {
+        // by default all moves are given a neutral score
+        0
+    }
+}
+
 /*
     Determine if a color is currently in check
 */
@@ -91,6 +114,105 @@ pub fn is_check(board: &BoardState, color: PieceColor) -> bool {
     }
 }
 
+/*
+    Find every piece giving check to the king of the given color, kings can never give
+    check so they are not considered
+
+    This is slower than is_check so it is only used where the checking pieces matter
+*/
+pub fn checkers(board: &BoardState, color: PieceColor) -> Vec<Point> {
+    let king = match color {
+        White => board.white_king_location,
+        Black => board.black_king_location,
+    };
+    let attacking_color = color.opposite();
+    let mut checkers = Vec::new();
+
+    let sliders = [
+        ((1, 0), Piece::rook(attacking_color)),
+        ((-1, 0), Piece::rook(attacking_color)),
+        ((0, 1), Piece::rook(attacking_color)),
+        ((0, -1), Piece::rook(attacking_color)),
+        ((1, -1), Piece::bishop(attacking_color)),
+        ((1, 1), Piece::bishop(attacking_color)),
+        ((-1, 1), Piece::bishop(attacking_color)),
+        ((-1, -1), Piece::bishop(attacking_color)),
+    ];
+    for ((r, c), slider) in sliders {
+        let mut row = king.0 as i8 + r;
+        let mut col = king.1 as i8 + c;
+        while board.board[row as usize][col as usize].is_empty() {
+            row += r;
+            col += c;
+        }
+        let square = board.board[row as usize][col as usize];
+        if square == slider || square == Piece::queen(attacking_color) {
+            checkers.push(Point(row as usize, col as usize));
+        }
+    }
+
+    for (r, c) in &KNIGHT_CORDS {
+        let point = Point((king.0 as i8 + r) as usize, (king.1 as i8 + c) as usize);
+        if board.board[point.0][point.1] == Piece::knight(attacking_color) {
+            checkers.push(point);
+        }
+    }
+
+    let pawn_row = match color {
+        White => king.0 - 1,
+        Black => king.0 + 1,
+    };
+    for col in [king.1 - 1, king.1 + 1] {
+        if board.board[pawn_row][col] == Piece::pawn(attacking_color) {
+            checkers.push(Point(pawn_row, col));
+        }
+    }
+    checkers
+}
+
+/*
+    Check if a move leaves the king of the player who moved in check
+
+    Only the squares are touched while testing the move, which is much cheaper than
+    a full make_move. Castling is not handled here since can_castle already checks it
+*/
+fn is_legal_move(board: &mut BoardState, mov: Move) -> bool {
+    let start = mov.start();
+    let end = mov.end();
+    let color = board.to_move;
+    let moved = board.board[start.0][start.1];
+    let captured = board.board[end.0][end.1];
+    // en passant removes a pawn beside the starting square
+    let en_passant_square = Point(start.0, end.1);
+    let en_passant_pawn = board.board[en_passant_square.0][en_passant_square.1];
+
+    board.board[end.0][end.1] = moved;
+    board.board[start.0][start.1] = Square::Empty;
+    if mov.is_en_passant() {
+        board.board[en_passant_square.0][en_passant_square.1] = Square::Empty;
+    }
+    let is_king = moved == Piece::king(color);
+    if is_king {
+        match color {
+            White => board.white_king_location = end,
+            Black => board.black_king_location = end,
+        }
+    }
+
+    let legal = !is_check(board, color);
+
+    board.board[start.0][start.1] = moved;
+    board.board[end.0][end.1] = captured;
+    board.board[en_passant_square.0][en_passant_square.1] = en_passant_pawn;
+    if is_king {
+        match color {
+            White => board.white_king_location = start,
+            Black => board.black_king_location = start,
+        }
+    }
+    legal
+}
+
 /*
     Generate pseudo-legal moves for a knight
 */
@@ -552,15 +674,14 @@ fn can_castle_black_queen_side(board: &BoardState) -> bool {
 }
 
 /*
-    Given the coordinates of a piece and that pieces color, generate all possible pseudo-legal moves for that piece
+    Given the coordinates of a piece and that pieces color, generate all legal moves for that piece
 */
 fn generate_moves_for_piece(
     piece: Piece,
-    board: &BoardState,
+    board: &mut BoardState,
     square_cords: Point,
-    new_moves: &mut Vec<BoardState>,
+    new_moves: &mut Vec<Move>,
     move_generation_mode: MoveGenerationMode,
-    zobrist_hasher: &ZobristHasher,
 ) {
     let mut moves: Vec<Point> = Vec::new();
     let Piece { color, kind } = piece;
@@ -573,130 +694,41 @@ fn generate_moves_for_piece(
         move_generation_mode,
     );
 
-    // make all the valid moves of this piece
-    for mov in moves {
-        let mut new_board = board.clone();
-        new_board.pawn_promotion = None;
-        new_board.swap_color(zobrist_hasher);
-
-        // update king location if we are moving the king
-        if kind == King {
-            match color {
-                White => new_board.white_king_location = mov,
-                Black => new_board.black_king_location = mov,
-            }
-        }
+    for target in moves {
+        let is_capture = !board.board[target.0][target.1].is_empty();
+        let is_promotion = kind == Pawn
+            && ((color == White && target.0 == BOARD_START)
+                || (color == Black && target.0 == BOARD_END - 1));
 
-        let target_square = new_board.board[mov.0][mov.1];
-        if let Square::Full(target_piece) = target_square {
-            new_board.order_heuristic = MVV_LVA[target_piece.index()][piece.index()];
+        let mov = if is_capture {
+            Move::capture(square_cords, target)
+        } else if kind == Pawn && (square_cords.0 as i8 - target.0 as i8).abs() == 2 {
+            Move::double_pawn_push(square_cords, target)
         } else {
-            // by default all moves are given a neutral score
-            new_board.order_heuristic = 0;
-        }
-
-        // move the piece, this will take care of any captures as well, excluding en passant
-        new_board.move_piece(square_cords, mov, zobrist_hasher);
-        new_board.last_move = Some((square_cords, mov));
+            Move::quiet(square_cords, target)
+        };
 
         // if you make your move, and you are in check, this move is not valid
-        if is_check(&new_board, color) {
+        if !is_legal_move(board, mov) {
             continue;
         }
 
-        // if the rook or king move, take away castling privileges
-        if kind == King {
-            if color == White {
-                new_board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-                new_board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-            } else {
-                new_board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-                new_board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-            }
-        } else if square_cords.0 == BOARD_END - 1 && square_cords.1 == BOARD_END - 1 {
-            new_board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-        } else if square_cords.0 == BOARD_END - 1 && square_cords.1 == BOARD_START {
-            new_board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-        } else if square_cords.0 == BOARD_START && square_cords.1 == BOARD_START {
-            new_board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-        } else if square_cords.0 == BOARD_START && square_cords.1 == BOARD_END - 1 {
-            new_board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-        }
-
-        // if the rook is captured, take away castling privileges
-        if mov.0 == BOARD_END - 1 && mov.1 == BOARD_END - 1 {
-            new_board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-        } else if mov.0 == BOARD_END - 1 && mov.1 == BOARD_START {
-            new_board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-        } else if mov.0 == BOARD_START && mov.1 == BOARD_START {
-            new_board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-        } else if mov.0 == BOARD_START && mov.1 == BOARD_END - 1 {
-            new_board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-        }
-
-        // checks if the pawn has moved two spaces, if it has it can be captured en passant, record the space *behind* the pawn ie the valid capture square
-        if move_generation_mode == MoveGenerationMode::AllMoves {
-            if kind == Pawn && (square_cords.0 as i8 - mov.0 as i8).abs() == 2 {
-                let en_passant_square = match color {
-                    White => Point(mov.0 + 1, mov.1),
-                    Black => Point(mov.0 - 1, mov.1),
-                };
-
-                new_board.pawn_double_move = Some(en_passant_square);
-                new_board.zobrist_key ^= zobrist_hasher.get_val_for_en_passant(en_passant_square.1);
-            } else {
-                // the most recent move was not a double pawn move, unset any possibly existing pawn double move
-                new_board.unset_pawn_double_move(zobrist_hasher);
-            }
-            // deal with pawn promotions
-            if mov.0 == BOARD_START && color == White && kind == Pawn {
-                promote_pawn(
-                    &new_board,
-                    White,
-                    square_cords,
-                    mov,
-                    new_moves,
-                    zobrist_hasher,
-                );
-            } else if mov.0 == BOARD_END - 1 && color == Black && kind == Pawn {
-                promote_pawn(
-                    &new_board,
-                    Black,
-                    square_cords,
-                    mov,
-                    new_moves,
-                    zobrist_hasher,
-                );
-            } else {
-                new_moves.push(new_board);
+        if is_promotion {
+            for promotion in [Queen, Knight, Bishop, Rook] {
+                new_moves.push(Move::promotion(square_cords, target, promotion, is_capture));
             }
         } else {
-            new_moves.push(new_board);
+            new_moves.push(mov);
         }
     }
 
     // take care of en passant captures
     if board.pawn_double_move.is_some() && kind == Pawn {
         let en_passant = pawn_moves_en_passant(piece, square_cords.0, square_cords.1, board);
-        if let Some(mov) = en_passant {
-            let mut new_board = board.clone();
-            new_board.last_move = Some((square_cords, mov));
-            new_board.swap_color(zobrist_hasher);
-            new_board.unset_pawn_double_move(zobrist_hasher);
-            new_board.move_piece(square_cords, mov, zobrist_hasher);
-            if color == White {
-                new_board.board[mov.0 + 1][mov.1] = Square::Empty;
-                new_board.zobrist_key ^=
-                    zobrist_hasher.get_val_for_piece(Piece::pawn(Black), Point(mov.0 + 1, mov.1));
-            } else {
-                new_board.board[mov.0 - 1][mov.1] = Square::Empty;
-                new_board.zobrist_key ^=
-                    zobrist_hasher.get_val_for_piece(Piece::pawn(White), Point(mov.0 - 1, mov.1));
-            }
-
-            // if you make a move, and you do not end up in check, then this move is valid
-            if !is_check(&new_board, board.to_move) {
-                new_moves.push(new_board);
+        if let Some(target) = en_passant {
+            let mov = Move::en_passant(square_cords, target);
+            if is_legal_move(board, mov) {
+                new_moves.push(mov);
             }
         }
     }
@@ -705,141 +737,34 @@ fn generate_moves_for_piece(
 /*
     Given the current board, attempt to castle
     If castling is possible add the move the the list of possible moves
-    Will also update appropriate castling variables if castling was successful
 */
-fn generate_castling_moves(
-    board: &BoardState,
-    new_moves: &mut Vec<BoardState>,
-    zobrist_hasher: &ZobristHasher,
-) {
-    if board.to_move == White && can_castle(board, &CastlingType::WhiteKingSide) {
-        let mut new_board = board.clone();
-        new_board.swap_color(zobrist_hasher);
-        new_board.unset_pawn_double_move(zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-        new_board.white_king_location = Point(BOARD_END - 1, BOARD_END - 2);
-        new_board.last_move = WHITE_KING_SIDE_CASTLE_ALG;
-
-        // move king and rook
-        new_board.move_piece(
-            board.white_king_location,
-            new_board.white_king_location,
-            zobrist_hasher,
-        );
-        new_board.move_piece(
-            Point(BOARD_END - 1, BOARD_END - 1),
-            Point(BOARD_END - 1, BOARD_END - 3),
-            zobrist_hasher,
-        );
-        new_moves.push(new_board);
-    }
-
-    if board.to_move == White && can_castle(board, &CastlingType::WhiteQueenSide) {
-        let mut new_board = board.clone();
-        new_board.swap_color(zobrist_hasher);
-        new_board.unset_pawn_double_move(zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-        new_board.white_king_location = Point(BOARD_END - 1, BOARD_START + 2);
-        new_board.last_move = WHITE_QUEEN_SIDE_CASTLE_ALG;
-
-        // move king and rook
-        new_board.move_piece(
-            board.white_king_location,
-            new_board.white_king_location,
-            zobrist_hasher,
-        );
-        new_board.move_piece(
-            Point(BOARD_END - 1, BOARD_START),
-            Point(BOARD_END - 1, BOARD_START + 3),
-            zobrist_hasher,
-        );
-        new_moves.push(new_board);
-    }
-
-    if board.to_move == Black && can_castle(board, &CastlingType::BlackKingSide) {
-        let mut new_board = board.clone();
-        new_board.swap_color(zobrist_hasher);
-        new_board.unset_pawn_double_move(zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-        new_board.black_king_location = Point(BOARD_START, BOARD_END - 2);
-        new_board.last_move = BLACK_KING_SIDE_CASTLE_ALG;
-        // move king and rook
-        new_board.move_piece(
-            board.black_king_location,
-            new_board.black_king_location,
-            zobrist_hasher,
-        );
-        new_board.move_piece(
-            Point(BOARD_START, BOARD_END - 1),
-            Point(BOARD_START, BOARD_END - 3),
-            zobrist_hasher,
-        );
-
-        new_moves.push(new_board);
-    }
-
-    if board.to_move == Black && can_castle(board, &CastlingType::BlackQueenSide) {
-        let mut new_board = board.clone();
-        new_board.swap_color(zobrist_hasher);
-        new_board.unset_pawn_double_move(zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-        new_board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-        new_board.black_king_location = Point(BOARD_START, BOARD_START + 2);
-        new_board.last_move = BLACK_QUEEN_SIDE_CASTLE_ALG;
-
-        // move king and rook
-        new_board.move_piece(
-            board.black_king_location,
-            new_board.black_king_location,
-            zobrist_hasher,
-        );
-        new_board.move_piece(
-            Point(BOARD_START, BOARD_START),
-            Point(BOARD_START, BOARD_START + 3),
-            zobrist_hasher,
-        );
-
-        new_moves.push(new_board);
-    }
-}
-
-/*
-    Executes a pawn promotion on the given cords
-
-    This function assumes that the board state is a valid pawn promotion and does not do additional checks
-*/
-const QUEEN_PROMOTION_SCORE: i32 = 800; // queen value - pawn value
-const UNDER_PROMOTION_SCORE: i32 = -999999999; // under promotions should be tried last
-fn promote_pawn(
-    board: &BoardState,
-    color: PieceColor,
-    start: Point,
-    target: Point,
-    moves: &mut Vec<BoardState>,
-    zobrist_hasher: &ZobristHasher,
-) {
-    for kind in [Queen, Knight, Bishop, Rook] {
-        let mut new_board = board.clone();
-        new_board.unset_pawn_double_move(zobrist_hasher);
-        let promotion_piece = Piece { color, kind };
-        new_board.board[target.0][target.1] = Square::Full(promotion_piece);
-        new_board.last_move = Some((start, target));
-        new_board.pawn_promotion = Some(promotion_piece);
-
-        // promoting to a piece that isn't a queen is rarely a good idea
-        new_board.order_heuristic = if kind == Queen {
-            QUEEN_PROMOTION_SCORE
-        } else {
-            UNDER_PROMOTION_SCORE
-        };
-
-        // erase pawn and add the promotion piece
-        new_board.zobrist_key ^= zobrist_hasher.get_val_for_piece(promotion_piece, target)
-            ^ zobrist_hasher.get_val_for_piece(Piece::pawn(color), target);
-        moves.push(new_board);
+fn generate_castling_moves(board: &BoardState, new_moves: &mut Vec<Move>) {
+    if board.to_move == White {
+        if can_castle(board, &CastlingType::WhiteKingSide) {
+            new_moves.push(Move::castle(
+                board.white_king_location,
+                Point(BOARD_END - 1, BOARD_END - 2),
+            ));
+        }
+        if can_castle(board, &CastlingType::WhiteQueenSide) {
+            new_moves.push(Move::castle(
+                board.white_king_location,
+                Point(BOARD_END - 1, BOARD_START + 2),
+            ));
+        }
+    } else {
+        if can_castle(board, &CastlingType::BlackKingSide) {
+            new_moves.push(Move::castle(
+                board.black_king_location,
+                Point(BOARD_START, BOARD_END - 2),
+            ));
+        }
+        if can_castle(board, &CastlingType::BlackQueenSide) {
+            new_moves.push(Move::castle(
+                board.black_king_location,
+                Point(BOARD_START, BOARD_START + 2),
+            ));
+        }
     }
 }
 
@@ -849,7 +774,7 @@ fn promote_pawn(
     Will generate up until cur_depth = depth
 */
 pub fn generate_moves_test(
-    board: &BoardState,
+    board: &mut BoardState,
     cur_depth: usize,
     depth: usize,
     move_counts: &mut [u32],
@@ -864,17 +789,19 @@ pub fn generate_moves_test(
         }
         return;
     }
-    let moves = generate_moves(board, MoveGenerationMode::AllMoves, zobrist_hasher);
+    let moves = generate_moves(board, MoveGenerationMode::AllMoves);
     move_counts[cur_depth] += moves.len() as u32;
     for mov in moves {
+        let undo = board.make_move(mov, zobrist_hasher);
         generate_moves_test(
-            &mov,
+            board,
             cur_depth + 1,
             depth,
             move_counts,
             should_evaluate,
             zobrist_hasher,
         );
+        board.unmake_move(mov, &undo);
     }
 }
 
@@ -1747,35 +1674,49 @@ mod tests {
 
     #[test]
     fn only_captures_correctly_counted() {
-        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-
         let b = BoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
             .unwrap();
         assert_eq!(
-            generate_moves(&b, MoveGenerationMode::CapturesOnly, &zobrist_hasher).len(),
+            generate_moves(&b, MoveGenerationMode::CapturesOnly).len(),
             0
         );
 
         let b = BoardState::from_fen("rnbqkbnr/pppppppp/2N5/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
             .unwrap();
         assert_eq!(
-            generate_moves(&b, MoveGenerationMode::CapturesOnly, &zobrist_hasher).len(),
+            generate_moves(&b, MoveGenerationMode::CapturesOnly).len(),
             4
         );
 
         let b = BoardState::from_fen("K1k4p/8/8/8/8/8/8/B6R w KQkq - 0 1").unwrap();
         assert_eq!(
-            generate_moves(&b, MoveGenerationMode::CapturesOnly, &zobrist_hasher).len(),
+            generate_moves(&b, MoveGenerationMode::CapturesOnly).len(),
             2
         );
 
         let b = BoardState::from_fen("5B2/8/8/2p4R/1PK5/3NQ3/8/2R5 w KQkq - 0 1").unwrap();
         assert_eq!(
-            generate_moves(&b, MoveGenerationMode::CapturesOnly, &zobrist_hasher).len(),
+            generate_moves(&b, MoveGenerationMode::CapturesOnly).len(),
             6
         );
     }
 
+    #[test]
+    fn checkers_found() {
+        let b = BoardState::from_fen("8/8/8/8/3K4/8/8/8 w - - 0 1").unwrap();
+        assert!(checkers(&b, White).is_empty());
+
+        // double check from a rook and a knight
+        let b = BoardState::from_fen("3r4/8/4n3/8/3K4/8/8/7k w - - 0 1").unwrap();
+        let mut found = checkers(&b, White);
+        found.sort_by_key(|point| (point.0, point.1));
+        assert_eq!(found, vec![Point(2, 5), Point(4, 6)]);
+
+        // the pawn and bishop are both checking, the rook is blocked
+        let b = BoardState::from_fen("4k3/3Pp3/8/7B/8/8/8/4R2K b - - 0 1").unwrap();
+        assert_eq!(checkers(&b, Black).len(), 2);
+    }
+
     #[test]
     fn mvv_lva_sanity() {
         assert!(MVV_LVA[Pawn.index()][Pawn.index()] > MVV_LVA[Pawn.index()][Bishop.index()]);
@@ -1789,10 +1730,11 @@ mod tests {
     #[test]
     fn perft_test_position_1() {
         let mut moves_states = [0; 5];
-        let b = BoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
-            .unwrap();
+        let mut b =
+            BoardState::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 5, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 5, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 20);
         assert_eq!(moves_states[1], 400);
         assert_eq!(moves_states[2], 8902);
@@ -1803,12 +1745,12 @@ mod tests {
     #[test]
     fn perft_test_position_2() {
         let mut moves_states = [0; 4];
-        let b = BoardState::from_fen(
+        let mut b = BoardState::from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
         )
         .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 4, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 4, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 48);
         assert_eq!(moves_states[1], 2039);
         assert_eq!(moves_states[2], 97862);
@@ -1818,9 +1760,9 @@ mod tests {
     #[test]
     fn perft_test_position_3() {
         let mut moves_states = [0; 5];
-        let b = BoardState::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        let mut b = BoardState::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 5, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 5, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 14);
         assert_eq!(moves_states[1], 191);
         assert_eq!(moves_states[2], 2812);
@@ -1831,12 +1773,12 @@ mod tests {
     #[test]
     fn perft_test_position_4() {
         let mut moves_states = [0; 4];
-        let b = BoardState::from_fen(
+        let mut b = BoardState::from_fen(
             "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
         )
         .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 4, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 4, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 6);
         assert_eq!(moves_states[1], 264);
         assert_eq!(moves_states[2], 9467);
@@ -1846,12 +1788,12 @@ mod tests {
     #[test]
     fn perft_test_position_4_mirrored() {
         let mut moves_states = [0; 4];
-        let b = BoardState::from_fen(
+        let mut b = BoardState::from_fen(
             "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
         )
         .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 4, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 4, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 6);
         assert_eq!(moves_states[1], 264);
         assert_eq!(moves_states[2], 9467);
@@ -1861,10 +1803,11 @@ mod tests {
     #[test]
     fn perft_test_position_5() {
         let mut moves_states = [0; 4];
-        let b = BoardState::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
-            .unwrap();
+        let mut b =
+            BoardState::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
+                .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 4, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 4, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 44);
         assert_eq!(moves_states[1], 1486);
         assert_eq!(moves_states[2], 62379);
@@ -1874,12 +1817,12 @@ mod tests {
     #[test]
     fn perft_test_position_6() {
         let mut moves_states = [0; 4];
-        let b = BoardState::from_fen(
+        let mut b = BoardState::from_fen(
             "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
         )
         .unwrap();
         let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
-        generate_moves_test(&b, 0, 4, &mut moves_states, false, &zobrist_hasher);
+        generate_moves_test(&mut b, 0, 4, &mut moves_states, false, &zobrist_hasher);
         assert_eq!(moves_states[0], 46);
         assert_eq!(moves_states[1], 2079);
         assert_eq!(moves_states[2], 89890);
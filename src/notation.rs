@@ -0,0 +1,269 @@
+pub use crate::board::*;
+pub use crate::move_generation::*;
+use crate::zobrist::ZobristHasher;
+
+/*
+    Standard Algebraic Notation, see https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+
+    Render a legal move from the given board as SAN, ex Nbd2, exd6, e8=Q+ or O-O-O
+*/
+pub fn move_to_san(board: &BoardState, mov: Move, zobrist_hasher: &ZobristHasher) -> String {
+    let start = mov.start();
+    let end = mov.end();
+    let mut san = String::new();
+
+    if mov.is_castle() {
+        if end.1 > start.1 {
+            san += "O-O";
+        } else {
+            san += "O-O-O";
+        }
+    } else {
+        let kind = match board.board[start.0][start.1] {
+            Square::Full(piece) => piece.kind,
+            _ => Pawn,
+        };
+
+        if kind == Pawn {
+            if mov.is_capture() {
+                san.push(file_char(start));
+            }
+        } else {
+            san += &kind.alg().to_uppercase();
+
+            // other pieces of the same kind that could also move to the target square
+            let others: Vec<Point> = generate_moves(board, MoveGenerationMode::AllMoves)
+                .into_iter()
+                .filter(|other| {
+                    other.end() == end
+                        && other.start() != start
+                        && board.board[other.start().0][other.start().1]
+                            == board.board[start.0][start.1]
+                })
+                .map(|other| other.start())
+                .collect();
+            if !others.is_empty() {
+                if others.iter().all(|other| other.1 != start.1) {
+                    san.push(file_char(start));
+                } else if others.iter().all(|other| other.0 != start.0) {
+                    san.push(rank_char(start));
+                } else {
+                    san.push(file_char(start));
+                    san.push(rank_char(start));
+                }
+            }
+        }
+
+        if mov.is_capture() {
+            san.push('x');
+        }
+        san += &end.to_string();
+
+        if let Some(kind) = mov.promotion_kind() {
+            san.push('=');
+            san += &kind.alg().to_uppercase();
+        }
+    }
+
+    let mut next_board = board.clone();
+    next_board.make_move(mov, zobrist_hasher);
+    if is_check(&next_board, next_board.to_move) {
+        if generate_moves(&next_board, MoveGenerationMode::AllMoves).is_empty() {
+            san.push('#');
+        } else {
+            san.push('+');
+        }
+    }
+
+    san
+}
+
+/*
+    Find the legal move described by the SAN string. Check and annotation suffixes
+    are ignored and unnecessary disambiguation is accepted, ex Ngf3 when only one
+    knight can reach f3
+*/
+pub fn san_to_move(board: &BoardState, san: &str) -> Result<Move, &'static str> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let moves = generate_moves(board, MoveGenerationMode::AllMoves);
+
+    let castle = match san {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = castle {
+        return moves
+            .into_iter()
+            .find(|mov| mov.is_castle() && (mov.end().1 > mov.start().1) == king_side)
+            .ok_or("Illegal move: castling is not allowed");
+    }
+
+    // split off the promotion, written either as e8=Q or e8Q
+    let (san, promotion) = match san.char_indices().last() {
+        Some((i, c)) if c.is_ascii_uppercase() && i > 0 => {
+            let kind = piece_kind_from_san_char(c).ok_or("Invalid promotion piece")?;
+            (san[..i].trim_end_matches('='), Some(kind))
+        }
+        _ => (san, None),
+    };
+
+    let (kind, san) = match san.chars().next() {
+        Some(c) if c.is_ascii_uppercase() => (
+            piece_kind_from_san_char(c).ok_or("Invalid piece")?,
+            &san[1..],
+        ),
+        _ => (Pawn, san),
+    };
+
+    let squares: String = san.chars().filter(|&c| c != 'x' && c != ':').collect();
+    if squares.len() < 2 || !squares.is_ascii() {
+        return Err("Invalid move: no target square");
+    }
+    let (disambiguation, target) = squares.split_at(squares.len() - 2);
+    let end: Point = target.parse()?;
+
+    let mut start_file = None;
+    let mut start_rank = None;
+    for c in disambiguation.chars() {
+        match c {
+            'a'..='h' => start_file = Some(c),
+            '1'..='8' => start_rank = Some(c),
+            _ => return Err("Invalid move: bad disambiguation"),
+        }
+    }
+
+    let mut candidates = moves.into_iter().filter(|mov| {
+        let start = mov.start();
+        board.board[start.0][start.1]
+            == Piece {
+                color: board.to_move,
+                kind,
+            }
+            && mov.end() == end
+            && mov.promotion_kind() == promotion
+            && start_file.is_none_or(|file| file == file_char(start))
+            && start_rank.is_none_or(|rank| rank == rank_char(start))
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some(mov), None) => Ok(mov),
+        (None, _) => Err("Illegal move"),
+        (Some(_), Some(_)) => Err("Ambiguous move"),
+    }
+}
+
+fn piece_kind_from_san_char(c: char) -> Option<PieceKind> {
+    match c {
+        'N' => Some(Knight),
+        'B' => Some(Bishop),
+        'R' => Some(Rook),
+        'Q' => Some(Queen),
+        'K' => Some(King),
+        _ => None,
+    }
+}
+
+fn file_char(point: Point) -> char {
+    (b'a' + (point.1 - BOARD_START) as u8) as char
+}
+
+fn rank_char(point: Point) -> char {
+    (b'0' + (BOARD_END - point.0) as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, uci_move: &str) -> String {
+        let board = BoardState::from_fen(fen).unwrap();
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mov = generate_moves(&board, MoveGenerationMode::AllMoves)
+            .into_iter()
+            .find(|mov| mov.to_string() == uci_move)
+            .unwrap();
+        move_to_san(&board, mov, &zobrist_hasher)
+    }
+
+    #[test]
+    fn pawn_moves_to_san() {
+        assert_eq!(san(DEFAULT_FEN_STRING, "e2e4"), "e4");
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(san(fen, "e5f6"), "exf6");
+        assert_eq!(san(fen, "e5e6"), "e6");
+    }
+
+    #[test]
+    fn piece_moves_disambiguated() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 1";
+        assert_eq!(san(fen, "b1d2"), "Nbd2");
+        assert_eq!(san(fen, "f3d2"), "Nfd2");
+        assert_eq!(san(fen, "b1c3"), "Nc3");
+
+        let fen = "7k/8/8/R7/8/8/8/R6K w - - 0 1";
+        assert_eq!(san(fen, "a1a3"), "R1a3");
+        assert_eq!(san(fen, "a5a3"), "R5a3");
+
+        let fen = "8/7k/8/8/8/Q6K/8/Q1Q5 w - - 0 1";
+        assert_eq!(san(fen, "a1b2"), "Qa1b2");
+        assert_eq!(san(fen, "c1b2"), "Qcb2");
+    }
+
+    #[test]
+    fn special_moves_to_san() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "e1g1"), "O-O");
+        assert_eq!(san(fen, "e1c1"), "O-O-O");
+        assert_eq!(san(fen, "a1a8"), "Rxa8+");
+
+        let fen = "3r3k/4P3/8/8/8/8/8/7K w - - 0 1";
+        assert_eq!(san(fen, "e7d8q"), "exd8=Q+");
+        assert_eq!(san(fen, "e7e8q"), "e8=Q+");
+        assert_eq!(san(fen, "e7e8n"), "e8=N");
+
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn san_parsed() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/5N2/PPP1PPPP/RNBQKB1R w KQkq - 0 1";
+        let board = BoardState::from_fen(fen).unwrap();
+        assert_eq!(san_to_move(&board, "Nbd2").unwrap().to_string(), "b1d2");
+        assert_eq!(san_to_move(&board, "Nb1c3").unwrap().to_string(), "b1c3");
+        assert_eq!(san_to_move(&board, "e4!?").unwrap().to_string(), "e2e4");
+        assert_eq!(san_to_move(&board, "Nd2"), Err("Ambiguous move"));
+        assert_eq!(san_to_move(&board, "Nb4"), Err("Illegal move"));
+        assert_eq!(
+            san_to_move(&board, "O-O"),
+            Err("Illegal move: castling is not allowed")
+        );
+        assert!(san_to_move(&board, "Zd4").is_err());
+        assert!(san_to_move(&board, "x").is_err());
+
+        let board = BoardState::from_fen("3r3k/4P3/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(san_to_move(&board, "exd8=Q+").unwrap().to_string(), "e7d8q");
+        assert_eq!(san_to_move(&board, "e8N").unwrap().to_string(), "e7e8n");
+        assert_eq!(san_to_move(&board, "0-0").unwrap().to_string(), "e1g1");
+        assert_eq!(san_to_move(&board, "e8"), Err("Illegal move"));
+    }
+
+    #[test]
+    fn san_round_trip() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        for fen in [
+            DEFAULT_FEN_STRING,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/7k/8/8/8/Q6K/8/Q1Q5 w - - 0 1",
+        ] {
+            let board = BoardState::from_fen(fen).unwrap();
+            for mov in generate_moves(&board, MoveGenerationMode::AllMoves) {
+                let san = move_to_san(&board, mov, &zobrist_hasher);
+                assert_eq!(san_to_move(&board, &san), Ok(mov), "{} in {}", san, fen);
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+pub use crate::move_generation::*;
+use crate::zobrist::ZobristHasher;
+
+/*
+    Counts of the moves played at a single depth of a perft run, matching the tables on
+    https://www.chessprogramming.org/Perft_Results so any mismatch can be narrowed down
+
+    A discovered check is a check given by any piece other than the one that moved,
+    a double check may also be counted as a discovered check
+*/
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    pub discovered_checks: u64,
+    pub double_checks: u64,
+    pub checkmates: u64,
+}
+
+/*
+    Count the number of leaf nodes reachable from this board in exactly depth moves
+*/
+pub fn perft(board: &mut BoardState, depth: u8, zobrist_hasher: &ZobristHasher) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = generate_moves(board, MoveGenerationMode::AllMoves);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mov in moves {
+        let undo = board.make_move(mov, zobrist_hasher);
+        nodes += perft(board, depth - 1, zobrist_hasher);
+        board.unmake_move(mov, &undo);
+    }
+    nodes
+}
+
+/*
+    Perft split by root move, used to find which move a wrong node count comes from
+    by comparing against another engine
+*/
+pub fn divide(
+    board: &mut BoardState,
+    depth: u8,
+    zobrist_hasher: &ZobristHasher,
+) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    generate_moves(board, MoveGenerationMode::AllMoves)
+        .into_iter()
+        .map(|mov| {
+            let undo = board.make_move(mov, zobrist_hasher);
+            let nodes = perft(board, depth - 1, zobrist_hasher);
+            board.unmake_move(mov, &undo);
+            (mov, nodes)
+        })
+        .collect()
+}
+
+/*
+    Collect the full statistics for every depth from 1 up to depth, the first entry
+    is for the moves from the starting board
+*/
+pub fn perft_stats(
+    board: &mut BoardState,
+    depth: u8,
+    zobrist_hasher: &ZobristHasher,
+) -> Vec<PerftStats> {
+    let mut stats = vec![PerftStats::default(); depth as usize];
+    collect_perft_stats(board, 0, &mut stats, zobrist_hasher);
+    stats
+}
+
+fn collect_perft_stats(
+    board: &mut BoardState,
+    ply: usize,
+    stats: &mut [PerftStats],
+    zobrist_hasher: &ZobristHasher,
+) {
+    for mov in generate_moves(board, MoveGenerationMode::AllMoves) {
+        let ply_stats = &mut stats[ply];
+        ply_stats.nodes += 1;
+        if mov.is_capture() {
+            ply_stats.captures += 1;
+        }
+        if mov.is_en_passant() {
+            ply_stats.en_passants += 1;
+        }
+        if mov.is_castle() {
+            ply_stats.castles += 1;
+        }
+        if mov.promotion_kind().is_some() {
+            ply_stats.promotions += 1;
+        }
+
+        let undo = board.make_move(mov, zobrist_hasher);
+        let checkers = checkers(board, board.to_move);
+        if !checkers.is_empty() {
+            ply_stats.checks += 1;
+            if checkers.iter().any(|&point| !moved_to(mov, point)) {
+                ply_stats.discovered_checks += 1;
+            }
+            if checkers.len() > 1 {
+                ply_stats.double_checks += 1;
+            }
+            if generate_moves(board, MoveGenerationMode::AllMoves).is_empty() {
+                ply_stats.checkmates += 1;
+            }
+        }
+        if ply + 1 < stats.len() {
+            collect_perft_stats(board, ply + 1, stats, zobrist_hasher);
+        }
+        board.unmake_move(mov, &undo);
+    }
+}
+
+// castling moves the rook as well as the king, so a check from the rook is not discovered
+fn moved_to(mov: Move, point: Point) -> bool {
+    let end = mov.end();
+    if mov.is_castle() {
+        let rook_col = if end.1 > mov.start().1 {
+            end.1 - 1
+        } else {
+            end.1 + 1
+        };
+        return point == Point(end.0, rook_col);
+    }
+    point == end
+}
+
+/*
+    Run a divide followed by the full statistics for each depth, returning the lines
+    to print so both the command line and UCI can show them
+*/
+pub fn perft_report(board: &BoardState, depth: u8, zobrist_hasher: &ZobristHasher) -> Vec<String> {
+    let mut board = board.clone();
+    let mut lines = Vec::new();
+    let mut total = 0;
+    for (mov, nodes) in divide(&mut board, depth, zobrist_hasher) {
+        lines.push(format!("{}: {}", mov, nodes));
+        total += nodes;
+    }
+    lines.push(String::new());
+    lines.push(format!("Nodes searched: {}", total));
+    lines.push(String::new());
+
+    lines.push(format!(
+        "{:>5} {:>12} {:>10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>8} {:>10}",
+        "Depth",
+        "Nodes",
+        "Captures",
+        "E.p.",
+        "Castles",
+        "Promotions",
+        "Checks",
+        "Discovered",
+        "Double",
+        "Checkmates"
+    ));
+    for (i, stats) in perft_stats(&mut board, depth, zobrist_hasher)
+        .iter()
+        .enumerate()
+    {
+        lines.push(format!(
+            "{:>5} {:>12} {:>10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>8} {:>10}",
+            i + 1,
+            stats.nodes,
+            stats.captures,
+            stats.en_passants,
+            stats.castles,
+            stats.promotions,
+            stats.checks,
+            stats.discovered_checks,
+            stats.double_checks,
+            stats.checkmates
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Table of values taken from https://www.chessprogramming.org/Perft_Results
+    #[allow(clippy::too_many_arguments)]
+    fn stats(
+        nodes: u64,
+        captures: u64,
+        en_passants: u64,
+        castles: u64,
+        promotions: u64,
+        checks: u64,
+        discovered_checks: u64,
+        double_checks: u64,
+        checkmates: u64,
+    ) -> PerftStats {
+        PerftStats {
+            nodes,
+            captures,
+            en_passants,
+            castles,
+            promotions,
+            checks,
+            discovered_checks,
+            double_checks,
+            checkmates,
+        }
+    }
+
+    fn run_perft_stats(fen: &str, depth: u8) -> Vec<PerftStats> {
+        let mut board = BoardState::from_fen(fen).unwrap();
+        perft_stats(&mut board, depth, &ZobristHasher::create_zobrist_hasher())
+    }
+
+    #[test]
+    fn perft_stats_start_position() {
+        assert_eq!(
+            run_perft_stats(DEFAULT_FEN_STRING, 4),
+            vec![
+                stats(20, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(400, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(8902, 34, 0, 0, 0, 12, 0, 0, 0),
+                stats(197281, 1576, 0, 0, 0, 469, 0, 0, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn perft_stats_kiwipete() {
+        assert_eq!(
+            run_perft_stats(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                3
+            ),
+            vec![
+                stats(48, 8, 0, 2, 0, 0, 0, 0, 0),
+                stats(2039, 351, 1, 91, 0, 3, 0, 0, 0),
+                stats(97862, 17102, 45, 3162, 0, 993, 0, 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn perft_stats_position_3() {
+        assert_eq!(
+            run_perft_stats("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4),
+            vec![
+                stats(14, 1, 0, 0, 0, 2, 0, 0, 0),
+                stats(191, 14, 0, 0, 0, 10, 0, 0, 0),
+                stats(2812, 209, 2, 0, 0, 267, 3, 0, 0),
+                stats(43238, 3348, 123, 0, 0, 1680, 106, 0, 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn perft_stats_position_4() {
+        assert_eq!(
+            run_perft_stats(
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                3
+            ),
+            vec![
+                stats(6, 0, 0, 0, 0, 0, 0, 0, 0),
+                stats(264, 87, 0, 6, 48, 10, 0, 0, 0),
+                stats(9467, 1021, 4, 0, 120, 38, 2, 0, 22),
+            ]
+        );
+    }
+
+    #[test]
+    fn divide_start_position() {
+        let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let split = divide(&mut board, 3, &zobrist_hasher);
+        assert_eq!(split.len(), 20);
+        assert_eq!(split.iter().map(|(_, nodes)| nodes).sum::<u64>(), 8902);
+        let e2e4 = split.iter().find(|(mov, _)| mov.to_string() == "e2e4");
+        assert_eq!(e2e4.unwrap().1, 600);
+        let g1h3 = split.iter().find(|(mov, _)| mov.to_string() == "g1h3");
+        assert_eq!(g1h3.unwrap().1, 400);
+    }
+
+    #[test]
+    fn perft_report_lists_root_moves_and_depths() {
+        let board = BoardState::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        let report = perft_report(&board, 2, &ZobristHasher::create_zobrist_hasher());
+        assert!(report.contains(&"Nodes searched: 191".to_string()));
+        assert!(report.contains(&"b4f4: 2".to_string()));
+        // 14 root moves, the total, the header and a row for each depth
+        assert_eq!(report.len(), 14 + 3 + 1 + 2);
+    }
+}
@@ -0,0 +1,865 @@
+pub use crate::board::PieceColor::*;
+pub use crate::board::*;
+use crate::engine::MATE_SCORE;
+use crate::notation::{move_to_san, san_to_move};
+use crate::search::MAX_DEPTH;
+use crate::zobrist::ZobristHasher;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// tags every PGN game must have, in the order they must be written
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// export format lines should be kept under 80 characters
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+pub struct PgnMove {
+    pub mov: Move,
+    pub san: String,
+    // numeric annotation glyphs, ex $1 for a good move, see https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    // alternatives to this move, each starting from the position before this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+/*
+    A problem found while reading a PGN file, games are numbered from 1 and ply
+    is the half move of the game the problem was found at, 0 for the tags
+*/
+#[derive(Debug, PartialEq, Eq)]
+pub struct PgnError {
+    pub game: usize,
+    pub ply: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Game {}, ply {}: {}", self.game, self.ply, self.message)
+    }
+}
+
+/*
+    A single game in Portable Game Notation
+    See http://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm
+*/
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start_board: BoardState,
+    pub moves: Vec<PgnMove>,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn new(start_board: &BoardState) -> PgnGame {
+        let mut game = PgnGame {
+            tags: Vec::new(),
+            start_board: start_board.clone(),
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        };
+        for tag in SEVEN_TAG_ROSTER {
+            game.set_tag(tag, "?");
+        }
+        game.set_tag("Date", &today());
+        game
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /*
+        Record a move, board is the position *before* the move is played
+    */
+    pub fn add_move(
+        &mut self,
+        board: &BoardState,
+        mov: Move,
+        comment: Option<String>,
+        zobrist_hasher: &ZobristHasher,
+    ) {
+        self.moves.push(PgnMove {
+            mov,
+            san: move_to_san(board, mov, zobrist_hasher),
+            nags: Vec::new(),
+            comment,
+            variations: Vec::new(),
+        });
+    }
+
+    // The starting position followed by the position after each move of the main line
+    pub fn positions(&self, zobrist_hasher: &ZobristHasher) -> Vec<BoardState> {
+        let mut board = self.start_board.clone();
+        let mut positions = vec![board.clone()];
+        for pgn_move in &self.moves {
+            board.make_move(pgn_move.mov, zobrist_hasher);
+            positions.push(board.clone());
+        }
+        positions
+    }
+
+    // Write the game out in the PGN export format
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.to_string(),
+                _ => self.tag(name).unwrap_or("?").to_string(),
+            };
+            pgn += &format_tag(name, &value);
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+                pgn += &format_tag(name, value);
+            }
+        }
+        let fen = self.start_board.to_fen();
+        if fen != DEFAULT_FEN_STRING {
+            pgn += &format_tag("SetUp", "1");
+            pgn += &format_tag("FEN", &fen);
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        movetext_tokens(
+            &self.moves,
+            self.start_board.full_move_number,
+            self.start_board.to_move,
+            &mut tokens,
+        );
+        tokens.push(self.result.to_string());
+
+        let mut line_length = 0;
+        let mut previous_token = "";
+        for token in &tokens {
+            if line_length > 0 && line_length + token.len() + 1 > MAX_LINE_LENGTH {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 && previous_token != "(" && token != ")" {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            line_length += token.len();
+            pgn += token;
+            previous_token = token;
+        }
+        pgn += "\n\n";
+        pgn
+    }
+}
+
+fn movetext_tokens(
+    moves: &[PgnMove],
+    mut move_number: u16,
+    mut to_move: PieceColor,
+    tokens: &mut Vec<String>,
+) {
+    let mut show_black_move_number = true;
+    for pgn_move in moves {
+        match to_move {
+            White => tokens.push(format!("{}.", move_number)),
+            Black if show_black_move_number => tokens.push(format!("{}...", move_number)),
+            Black => {}
+        }
+        tokens.push(pgn_move.san.clone());
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        show_black_move_number = false;
+        if let Some(comment) = &pgn_move.comment {
+            tokens.push(format!("{{{}}}", comment));
+            // the move number is repeated for black after a comment or variation
+            show_black_move_number = true;
+        }
+        for variation in &pgn_move.variations {
+            tokens.push("(".to_string());
+            movetext_tokens(variation, move_number, to_move, tokens);
+            tokens.push(")".to_string());
+            show_black_move_number = true;
+        }
+        if to_move == Black {
+            move_number += 1;
+        }
+        to_move = to_move.opposite();
+    }
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+    format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    Result(GameResult),
+    San(String),
+}
+
+/*
+    Read every game from the text of a PGN file. A game with an illegal or ambiguous
+    move is reported as an error without stopping the games after it from being read
+
+    Variations are checked and kept with the move they are an alternative to when
+    keep_variations is set, otherwise they are skipped entirely
+*/
+pub fn read_pgn(text: &str, keep_variations: bool) -> Vec<Result<PgnGame, PgnError>> {
+    let tokens = tokenize(text);
+    let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+    let mut games = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let game_number = games.len() + 1;
+        let mut reader = GameReader {
+            tokens: &tokens,
+            i,
+            game_number,
+            keep_variations,
+            zobrist_hasher: &zobrist_hasher,
+        };
+        let game = reader.read_game();
+        i = reader.i;
+        if let Some(game) = game {
+            games.push(game);
+        }
+    }
+    games
+}
+
+struct GameReader<'a> {
+    tokens: &'a [Token],
+    i: usize,
+    game_number: usize,
+    keep_variations: bool,
+    zobrist_hasher: &'a ZobristHasher,
+}
+
+impl GameReader<'_> {
+    // Returns None if there was nothing but comments left to read
+    fn read_game(&mut self) -> Option<Result<PgnGame, PgnError>> {
+        let mut tags = Vec::new();
+        while let Some(Token::Tag(name, value)) = self.tokens.get(self.i) {
+            tags.push((name.clone(), value.clone()));
+            self.i += 1;
+        }
+
+        let start_board = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => BoardState::from_fen(fen),
+            None => BoardState::from_fen(DEFAULT_FEN_STRING),
+        };
+        let start_board = match start_board {
+            Ok(board) => board,
+            Err(err) => return Some(Err(self.skip_game(0, err))),
+        };
+
+        let first_token = self.i;
+        let moves = match self.read_line(&start_board, 0) {
+            Ok(moves) => moves,
+            Err(err) => return Some(Err(self.skip_game_with_error(err))),
+        };
+
+        let result = match self.tokens.get(self.i) {
+            Some(Token::Result(result)) => {
+                self.i += 1;
+                *result
+            }
+            Some(Token::VariationEnd) => {
+                return Some(Err(
+                    self.skip_game(moves.len(), "Unexpected end of variation")
+                ))
+            }
+            _ => {
+                if tags.is_empty() && moves.is_empty() && self.i > first_token {
+                    // only stray comments or annotations were found
+                    return None;
+                }
+                match tags.iter().find(|(name, _)| name == "Result") {
+                    Some((_, result)) => parse_result(result).unwrap_or(GameResult::Unknown),
+                    None => GameResult::Unknown,
+                }
+            }
+        };
+
+        Some(Ok(PgnGame {
+            tags,
+            start_board,
+            moves,
+            result,
+        }))
+    }
+
+    /*
+        Read moves until the end of the game or variation, ply is the number of half
+        moves played before the first move of this line
+    */
+    fn read_line(
+        &mut self,
+        start_board: &BoardState,
+        ply: usize,
+    ) -> Result<Vec<PgnMove>, PgnError> {
+        let mut board = start_board.clone();
+        let mut previous_board: Option<BoardState> = None;
+        let mut moves: Vec<PgnMove> = Vec::new();
+        while let Some(token) = self.tokens.get(self.i) {
+            match token {
+                Token::Tag(..) | Token::Result(_) | Token::VariationEnd => break,
+                Token::VariationStart => {
+                    self.i += 1;
+                    let variation_ply = ply + moves.len();
+                    let previous_board = match &previous_board {
+                        Some(board) => board,
+                        None => {
+                            return Err(self.error(variation_ply, "Variation before first move"))
+                        }
+                    };
+                    if self.keep_variations {
+                        let variation = self.read_line(previous_board, variation_ply - 1)?;
+                        if let Some(last_move) = moves.last_mut() {
+                            last_move.variations.push(variation);
+                        }
+                    } else {
+                        self.skip_variation();
+                    }
+                    match self.tokens.get(self.i) {
+                        Some(Token::VariationEnd) => self.i += 1,
+                        _ => return Err(self.error(variation_ply, "Unterminated variation")),
+                    }
+                    continue;
+                }
+                Token::Comment(comment) => {
+                    // comments before the first move have nothing to be attached to
+                    if let Some(last_move) = moves.last_mut() {
+                        last_move.comment = match &last_move.comment {
+                            Some(existing) => Some(format!("{} {}", existing, comment)),
+                            None => Some(comment.clone()),
+                        };
+                    }
+                }
+                Token::Nag(nag) => {
+                    if let Some(last_move) = moves.last_mut() {
+                        last_move.nags.push(*nag);
+                    }
+                }
+                Token::San(san) => {
+                    let move_ply = ply + moves.len() + 1;
+                    let mov = match san_to_move(&board, san) {
+                        Ok(mov) => mov,
+                        Err(err) => {
+                            return Err(self.error(move_ply, &format!("{} {}", err, san)));
+                        }
+                    };
+                    moves.push(PgnMove {
+                        mov,
+                        san: move_to_san(&board, mov, self.zobrist_hasher),
+                        nags: Vec::new(),
+                        comment: None,
+                        variations: Vec::new(),
+                    });
+                    previous_board = Some(board.clone());
+                    board.make_move(mov, self.zobrist_hasher);
+                }
+            }
+            self.i += 1;
+        }
+        Ok(moves)
+    }
+
+    // Move past a variation without reading it, stops on the closing parenthesis
+    fn skip_variation(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.tokens.get(self.i) {
+            match token {
+                Token::VariationStart => depth += 1,
+                Token::VariationEnd if depth == 0 => return,
+                Token::VariationEnd => depth -= 1,
+                Token::Tag(..) | Token::Result(_) => return,
+                _ => {}
+            }
+            self.i += 1;
+        }
+    }
+
+    fn error(&self, ply: usize, message: &str) -> PgnError {
+        PgnError {
+            game: self.game_number,
+            ply,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_game(&mut self, ply: usize, message: &str) -> PgnError {
+        let err = self.error(ply, message);
+        self.skip_game_with_error(err)
+    }
+
+    // Move on to the start of the next game so one bad game doesn't spoil the rest
+    fn skip_game_with_error(&mut self, err: PgnError) -> PgnError {
+        while let Some(token) = self.tokens.get(self.i) {
+            self.i += 1;
+            match token {
+                Token::Result(_) => break,
+                Token::Tag(..) => {
+                    // the next game started without this one being terminated
+                    self.i -= 1;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        err
+    }
+}
+
+fn parse_result(result: &str) -> Option<GameResult> {
+    match result {
+        "1-0" => Some(GameResult::WhiteWins),
+        "0-1" => Some(GameResult::BlackWins),
+        "1/2-1/2" => Some(GameResult::Draw),
+        "*" => Some(GameResult::Unknown),
+        _ => None,
+    }
+}
+
+// Annotations written as suffixes have an equivalent NAG, ex e4! is the same as e4 $1
+fn nag_from_suffix(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line_start = true;
+            continue;
+        }
+        let at_line_start = line_start;
+        line_start = false;
+        match c {
+            _ if c.is_whitespace() => {}
+            // escaped lines are for use by other programs and are skipped
+            '%' if at_line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line_start = true;
+                        break;
+                    }
+                }
+            }
+            '[' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' || c == ']' {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                let mut value = String::new();
+                let mut in_quotes = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => in_quotes = !in_quotes,
+                        '\\' if in_quotes => {
+                            if let Some(escaped) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        ']' if !in_quotes => break,
+                        _ if in_quotes => value.push(c),
+                        _ => {}
+                    }
+                }
+                tokens.push(Token::Tag(name, value));
+            }
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                line_start = true;
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '$' => {
+                let mut nag = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    nag.push(c);
+                    chars.next();
+                }
+                if let Ok(nag) = nag.parse() {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+            _ => {
+                let mut symbol = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[]{}();$".contains(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+                symbol_tokens(&symbol, &mut tokens);
+            }
+        }
+    }
+    tokens
+}
+
+// Split a symbol like 12.Nf3!? into the move and its annotation, move numbers are dropped
+fn symbol_tokens(symbol: &str, tokens: &mut Vec<Token>) {
+    if let Some(result) = parse_result(symbol) {
+        tokens.push(Token::Result(result));
+        return;
+    }
+
+    let san = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = if san.starts_with('.') {
+        san.trim_start_matches('.')
+    } else {
+        // castling written with zeros also starts with a digit
+        symbol
+    };
+    if san.is_empty() {
+        return;
+    }
+
+    let annotation_start = san.trim_end_matches(['!', '?']).len();
+    let (san, suffix) = san.split_at(annotation_start);
+    if !san.is_empty() {
+        tokens.push(Token::San(san.to_string()));
+    }
+    if let Some(nag) = nag_from_suffix(suffix) {
+        tokens.push(Token::Nag(nag));
+    }
+}
+
+/*
+    Format a search result as a PGN comment, ex +0.35/12 or -M3/9
+
+    The score is in centipawns from the perspective of the player who made the move
+*/
+pub fn eval_comment(score: i32, depth: u8) -> String {
+    let mate_threshold = MATE_SCORE - MAX_DEPTH as i32;
+    if score >= mate_threshold {
+        format!("+M{}/{}", (MATE_SCORE - score + 1) / 2, depth)
+    } else if score <= -mate_threshold {
+        format!("-M{}/{}", (MATE_SCORE + score + 1) / 2, depth)
+    } else {
+        format!("{:+.2}/{}", score as f64 / 100.0, depth)
+    }
+}
+
+// The current date in the format PGN expects, ex 2021.03.24
+fn today() -> String {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => date_from_days((duration.as_secs() / 86400) as i64),
+        Err(_) => "????.??.??".to_string(),
+    }
+}
+
+/*
+    Convert a count of days since 1970-01-01 into a date
+    See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+*/
+fn date_from_days(days: i64) -> String {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::{generate_moves, MoveGenerationMode};
+
+    fn play_moves(game: &mut PgnGame, moves: &[&str]) {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut board = game.start_board.clone();
+        for uci_move in moves {
+            let mov = generate_moves(&board, MoveGenerationMode::AllMoves)
+                .into_iter()
+                .find(|mov| mov.to_string() == *uci_move)
+                .unwrap();
+            game.add_move(&board, mov, None, &zobrist_hasher);
+            board.make_move(mov, &zobrist_hasher);
+        }
+    }
+
+    #[test]
+    fn export_game_from_start_position() {
+        let mut game = PgnGame::new(&BoardState::from_fen(DEFAULT_FEN_STRING).unwrap());
+        game.set_tag("Date", "2021.03.24");
+        game.set_tag("White", "walleye");
+        game.set_tag("TimeControl", "1");
+        play_moves(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        game.result = GameResult::BlackWins;
+        assert_eq!(
+            game.to_pgn(),
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"2021.03.24\"]\n\
+             [Round \"?\"]\n\
+             [White \"walleye\"]\n\
+             [Black \"?\"]\n\
+             [Result \"0-1\"]\n\
+             [TimeControl \"1\"]\n\
+             \n\
+             1. f3 e5 2. g4 Qh4# 0-1\n\n"
+        );
+    }
+
+    #[test]
+    fn export_game_from_fen() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 4 20";
+        let mut game = PgnGame::new(&BoardState::from_fen(fen).unwrap());
+        play_moves(&mut game, &["e8g8", "e1c1"]);
+        game.moves[0].comment = Some(eval_comment(-35, 7));
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 4 20\"]\n"));
+        assert!(pgn.ends_with("\n20... O-O {-0.35/7} 21. O-O-O *\n\n"));
+    }
+
+    #[test]
+    fn long_games_wrapped() {
+        let mut game = PgnGame::new(&BoardState::from_fen(DEFAULT_FEN_STRING).unwrap());
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves: Vec<&str> = shuffle.iter().cycle().take(40).copied().collect();
+        play_moves(&mut game, &moves);
+        for mov in &mut game.moves {
+            mov.comment = Some(eval_comment(0, 10));
+        }
+        let pgn = game.to_pgn();
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(pgn.contains("20... Ng8 {+0.00/10} *"));
+    }
+
+    #[test]
+    fn eval_comments() {
+        assert_eq!(eval_comment(35, 12), "+0.35/12");
+        assert_eq!(eval_comment(-120, 3), "-1.20/3");
+        assert_eq!(eval_comment(MATE_SCORE - 5, 6), "+M3/6");
+        assert_eq!(eval_comment(-MATE_SCORE + 4, 6), "-M2/6");
+    }
+
+    fn read_one(text: &str, keep_variations: bool) -> PgnGame {
+        let mut games = read_pgn(text, keep_variations);
+        assert_eq!(games.len(), 1);
+        games.remove(0).unwrap()
+    }
+
+    #[test]
+    fn import_tags_and_moves() {
+        let game = read_one(
+            "[Event \"F/S Return Match\"]\n\
+             [Site \"Belgrade, Serbia JUG\"]\n\
+             [Date \"1992.11.04\"]\n\
+             [Round \"29\"]\n\
+             [White \"Fischer, Robert J.\"]\n\
+             [Black \"Spassky, Boris V.\"]\n\
+             [Result \"1/2-1/2\"]\n\
+             [Annotator \"Someone \\\"quoted\\\"\"]\n\
+             \n\
+             1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6\n\
+             4.Ba4 Nf6 5. O-O Be7 1/2-1/2\n",
+            false,
+        );
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.moves.len(), 10);
+        assert_eq!(
+            game.moves[4].comment.as_deref(),
+            Some("This opening is called the Ruy Lopez.")
+        );
+        assert_eq!(game.moves[8].san, "O-O");
+        assert_eq!(game.moves[8].mov.to_string(), "e1g1");
+
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let positions = game.positions(&zobrist_hasher);
+        assert_eq!(positions.len(), 11);
+        assert_eq!(
+            positions[10].to_fen(),
+            "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6"
+        );
+    }
+
+    #[test]
+    fn import_annotations() {
+        let game = read_one(
+            "% an escaped line that should be skipped\n\
+             1. e4! $14 e5?! ; a rest of line comment\n\
+             2. Nf3 {first} {second} Nc6 *",
+            false,
+        );
+        assert_eq!(game.moves[0].nags, vec![1, 14]);
+        assert_eq!(game.moves[1].nags, vec![6]);
+        assert_eq!(
+            game.moves[1].comment.as_deref(),
+            Some("a rest of line comment")
+        );
+        assert_eq!(game.moves[2].comment.as_deref(), Some("first second"));
+        assert_eq!(game.result, GameResult::Unknown);
+    }
+
+    #[test]
+    fn import_variations() {
+        let text = "1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) (1... e6) 2. Nf3 1-0";
+
+        let game = read_one(text, false);
+        assert_eq!(game.moves.len(), 3);
+        assert!(game.moves[1].variations.is_empty());
+        assert_eq!(game.result, GameResult::WhiteWins);
+
+        let game = read_one(text, true);
+        assert_eq!(game.moves.len(), 3);
+        let variations = &game.moves[1].variations;
+        assert_eq!(variations.len(), 2);
+        assert_eq!(variations[0].len(), 3);
+        assert_eq!(variations[0][0].san, "c5");
+        assert_eq!(variations[0][1].variations[0][1].san, "d5");
+        assert_eq!(variations[1][0].san, "e6");
+
+        // variations are written back out with the game
+        assert!(game
+            .to_pgn()
+            .ends_with("1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) (1... e6) 2. Nf3 1-0\n\n"));
+    }
+
+    #[test]
+    fn import_multiple_games() {
+        let text = "[Event \"one\"]\n1. d4 d5 0-1\n\n\
+                    [Event \"two\"]\n[SetUp \"1\"]\n[FEN \"5k2/8/8/8/8/8/8/4K2R w K - 0 30\"]\n\
+                    30. O-O+ Kg7 *\n\n\
+                    [Event \"three\"]\n1. e4 1-0";
+        let games = read_pgn(text, false);
+        assert_eq!(games.len(), 3);
+        let second = games[1].as_ref().unwrap();
+        assert_eq!(second.tag("Event"), Some("two"));
+        assert_eq!(second.moves[0].san, "O-O+");
+        assert_eq!(second.moves[1].mov.to_string(), "f8g7");
+        assert_eq!(games[2].as_ref().unwrap().moves.len(), 1);
+    }
+
+    #[test]
+    fn import_errors_reported() {
+        let text = "[Event \"good\"]\n1. e4 e5 *\n\n\
+                    [Event \"illegal\"]\n1. e4 e5 2. Ke3 Nc6 *\n\n\
+                    [Event \"ambiguous\"]\n1. Nf3 d5 2. d3 e5 3. Nd2 *\n\n\
+                    [Event \"bad variation\"]\n1. e4 (1. d4 d5 2. Qd5) e5 *\n\n\
+                    [Event \"bad fen\"]\n[FEN \"not a fen\"]\n1. e4 *\n\n\
+                    [Event \"good\"]\n1. d4 *";
+        let games = read_pgn(text, true);
+        assert_eq!(games.len(), 6);
+        assert!(games[0].is_ok());
+        assert_eq!(
+            games[1].as_ref().err(),
+            Some(&PgnError {
+                game: 2,
+                ply: 3,
+                message: "Illegal move Ke3".to_string()
+            })
+        );
+        assert_eq!(games[2].as_ref().err().unwrap().ply, 5);
+        assert_eq!(
+            games[2].as_ref().err().unwrap().message,
+            "Ambiguous move Nd2"
+        );
+        assert_eq!(games[3].as_ref().err().unwrap().ply, 3);
+        assert_eq!(games[4].as_ref().err().unwrap().ply, 0);
+        assert_eq!(games[5].as_ref().unwrap().moves.len(), 1);
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game = PgnGame::new(&BoardState::from_fen(fen).unwrap());
+        play_moves(
+            &mut game,
+            &["e1g1", "a6e2", "c3e2", "h3g2", "e2f4", "g2f1q"],
+        );
+        game.moves[2].comment = Some(eval_comment(120, 5));
+        game.moves[3].nags.push(2);
+        let pgn = game.to_pgn();
+
+        let imported = read_one(&pgn, true);
+        assert_eq!(imported.to_pgn(), pgn);
+        assert_eq!(
+            imported
+                .positions(&zobrist_hasher)
+                .last()
+                .unwrap()
+                .zobrist_key,
+            game.positions(&zobrist_hasher).last().unwrap().zobrist_key
+        );
+    }
+
+    #[test]
+    fn dates_calculated() {
+        assert_eq!(date_from_days(0), "1970.01.01");
+        assert_eq!(date_from_days(19723), "2024.01.01");
+        assert_eq!(date_from_days(19782), "2024.02.29");
+    }
+}
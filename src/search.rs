@@ -1,59 +1,775 @@
 pub use crate::board::*;
+use crate::history::History;
+use crate::time_control::GameTime;
+use crate::utils::out_of_time;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 pub const MAX_DEPTH: u8 = 100;
 pub const KILLER_MOVE_PLY_SIZE: usize = 2;
-type MoveArray = [Option<(Point, Point)>; MAX_DEPTH as usize];
-type KillerMoveArray =
-    [[Option<(Point, Point)>; KILLER_MOVE_PLY_SIZE]; MAX_DEPTH as usize];
+pub const DEFAULT_THREADS: usize = 1;
+pub const MIN_THREADS: usize = 1;
+pub const MAX_THREADS: usize = 64;
+pub const DEFAULT_MULTI_PV: usize = 1;
+pub const MIN_MULTI_PV: usize = 1;
+pub const MAX_MULTI_PV: usize = 256;
+// nodes are added to the shared count in batches so the threads aren't all writing to it constantly
+const NODE_BATCH_SIZE: u64 = 1024;
+// the most plies a single line can be extended by
+const MAX_LINE_EXTENSIONS: u8 = 16;
+pub type MoveArray = [Option<Move>; MAX_DEPTH as usize];
+type PvTable = [MoveArray; MAX_DEPTH as usize];
+type KillerMoveArray = [[Option<Move>; KILLER_MOVE_PLY_SIZE]; MAX_DEPTH as usize];
+
+/*
+    When to give up searching, the search stops as soon as any one limit is reached
+    With no limits at all the search continues until it is aborted or reaches MAX_DEPTH
+
+    Also how many of the best lines to report, which comes from the MultiPV option,
+    and which root moves to consider when not all of them should be searched
+*/
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchLimits {
+    pub depth: u8,
+    pub nodes: u64,
+    pub movetime: Option<u128>,
+    // stop once a mate in this many moves or fewer is found
+    pub mate: Option<u8>,
+    // the time left on the clocks, a share of which is used for this move
+    pub clock: Option<GameTime>,
+    // search until told to stop, ignoring the clock and movetime
+    pub infinite: bool,
+    // search the position after the expected reply, until the GUI sends ponderhit or stop
+    pub ponder: bool,
+    pub multi_pv: usize,
+    // only these root moves are searched, every legal move when empty
+    pub search_moves: Vec<Move>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> SearchLimits {
+        SearchLimits {
+            depth: MAX_DEPTH - 1,
+            nodes: u64::MAX,
+            movetime: None,
+            mate: None,
+            clock: None,
+            infinite: false,
+            ponder: false,
+            multi_pv: DEFAULT_MULTI_PV,
+            search_moves: Vec::new(),
+        }
+    }
+}
+
+impl SearchLimits {
+    // only limited by time, as when playing a game
+    pub fn time(time_to_move_ms: u128) -> SearchLimits {
+        SearchLimits {
+            movetime: Some(time_to_move_ms),
+            ..SearchLimits::default()
+        }
+    }
+
+    // how long the given color may search for, the shorter of movetime and the clock
+    pub fn time_to_move_ms(&self, color: PieceColor) -> u128 {
+        if self.infinite {
+            return u128::MAX;
+        }
+        let clock_time = self.clock.map(|clock| clock.calculate_time_slice(color));
+        match (self.movetime, clock_time) {
+            (Some(movetime), Some(clock_time)) => movetime.min(clock_time),
+            (Some(time), None) | (None, Some(time)) => time,
+            (None, None) => u128::MAX,
+        }
+    }
+
+    // a mate in the given number of plies satisfies the mate limit
+    pub fn is_mate_found(&self, mate_in_plies: Option<u8>) -> bool {
+        match (self.mate, mate_in_plies) {
+            (Some(moves), Some(plies)) => (plies as u32) < 2 * moves as u32,
+            _ => false,
+        }
+    }
+}
+
+/*
+    Values that control how much the search reduces and prunes. They can be changed with
+    UCI options that the uci command doesn't list, so they can be tuned with self play
+    without cluttering the options shown in a GUI
+
+    Fractions are kept in hundredths so every option is a whole number
+*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SearchParams {
+    // late move reductions, https://www.chessprogramming.org/Late_Move_Reductions
+    pub lmr_min_depth: i32,
+    pub lmr_min_moves: i32,
+    pub lmr_base: i32,
+    pub lmr_divisor: i32,
+    // late move pruning, quiet moves past base + factor * depth^2 aren't searched
+    pub lmp_max_depth: i32,
+    pub lmp_base: i32,
+    pub lmp_depth_factor: i32,
+    // reverse futility pruning, https://www.chessprogramming.org/Reverse_Futility_Pruning
+    pub rfp_max_depth: i32,
+    pub rfp_margin: i32,
+    // futility pruning, https://www.chessprogramming.org/Futility_Pruning
+    pub futility_max_depth: i32,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    // razoring, https://www.chessprogramming.org/Razoring
+    pub razor_max_depth: i32,
+    pub razor_base: i32,
+    pub razor_margin: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> SearchParams {
+        SearchParams {
+            lmr_min_depth: 3,
+            lmr_min_moves: 3,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            lmp_max_depth: 3,
+            lmp_base: 3,
+            lmp_depth_factor: 2,
+            rfp_max_depth: 6,
+            rfp_margin: 80,
+            futility_max_depth: 3,
+            futility_base: 100,
+            futility_margin: 100,
+            razor_max_depth: 3,
+            razor_base: 250,
+            razor_margin: 150,
+        }
+    }
+}
+
+// an option's name, minimum, maximum and the field it sets
+type SearchOption = (&'static str, i32, i32, fn(&mut SearchParams) -> &mut i32);
+
+impl SearchParams {
+    // the name, minimum, maximum and field of every option
+    pub const OPTIONS: [SearchOption; 15] = [
+        ("LmrMinDepth", 1, 20, |params| &mut params.lmr_min_depth),
+        ("LmrMinMoves", 1, 64, |params| &mut params.lmr_min_moves),
+        ("LmrBase", 0, 500, |params| &mut params.lmr_base),
+        ("LmrDivisor", 50, 1000, |params| &mut params.lmr_divisor),
+        ("LmpMaxDepth", 0, 20, |params| &mut params.lmp_max_depth),
+        ("LmpBase", 1, 64, |params| &mut params.lmp_base),
+        ("LmpDepthFactor", 0, 64, |params| {
+            &mut params.lmp_depth_factor
+        }),
+        ("RfpMaxDepth", 0, 20, |params| &mut params.rfp_max_depth),
+        ("RfpMargin", 0, 1000, |params| &mut params.rfp_margin),
+        ("FutilityMaxDepth", 0, 20, |params| {
+            &mut params.futility_max_depth
+        }),
+        ("FutilityBase", 0, 1000, |params| &mut params.futility_base),
+        ("FutilityMargin", 0, 1000, |params| {
+            &mut params.futility_margin
+        }),
+        ("RazorMaxDepth", 0, 20, |params| &mut params.razor_max_depth),
+        ("RazorBase", 0, 2000, |params| &mut params.razor_base),
+        ("RazorMargin", 0, 1000, |params| &mut params.razor_margin),
+    ];
+
+    // option names are case insensitive, as with every UCI option
+    pub fn set(&mut self, name: &str, value: i32) -> Result<(), &'static str> {
+        let (_, min, max, field) = SearchParams::OPTIONS
+            .iter()
+            .find(|(option, _, _, _)| option.eq_ignore_ascii_case(name))
+            .ok_or("Unknown search option")?;
+        if value < *min || value > *max {
+            return Err("Search option value out of range");
+        }
+        *field(self) = value;
+        Ok(())
+    }
+
+    /*
+        How many plies less to search a quiet move ordered late, grows with the log of both
+        the depth and how many moves came before it
+    */
+    pub fn late_move_reduction(&self, depth: u8, move_number: usize) -> u8 {
+        if (depth as i32) < self.lmr_min_depth || (move_number as i32) < self.lmr_min_moves {
+            return 0;
+        }
+        let reduction = self.lmr_base as f64 / 100.0
+            + (depth as f64).ln() * (move_number as f64).ln() / (self.lmr_divisor as f64 / 100.0);
+        // always leave at least one ply to search
+        (reduction as u8).min(depth.saturating_sub(2))
+    }
+
+    // quiet moves after this many are skipped at shallow depths
+    pub fn late_move_count(&self, depth: u8) -> Option<usize> {
+        if depth as i32 > self.lmp_max_depth {
+            return None;
+        }
+        Some((self.lmp_base + self.lmp_depth_factor * depth as i32 * depth as i32) as usize)
+    }
+
+    // a static evaluation this far above beta is taken as a fail high without searching
+    pub fn reverse_futility_margin(&self, depth: u8) -> Option<i32> {
+        if depth as i32 > self.rfp_max_depth {
+            return None;
+        }
+        Some(self.rfp_margin * depth as i32)
+    }
+
+    // quiet moves can't raise a static evaluation this far below alpha back above it
+    pub fn futility_margin(&self, depth: u8) -> Option<i32> {
+        if depth as i32 > self.futility_max_depth {
+            return None;
+        }
+        Some(self.futility_base + self.futility_margin * depth as i32)
+    }
+
+    // a static evaluation this far below alpha drops straight into quiescence
+    pub fn razor_margin(&self, depth: u8) -> Option<i32> {
+        if depth as i32 > self.razor_max_depth {
+            return None;
+        }
+        Some(self.razor_base + self.razor_margin * depth as i32)
+    }
+}
+
+/*
+    State shared by every thread working on the same search. Only the main thread
+    checks the search limits, it then tells the helper threads to finish with the stop flag
+
+    Abort is set from outside the search to end it early, ex by the UCI stop command
+    A new one is needed for every search
+
+    While pondering none of the limits are checked, once the expected move is played
+    the time spent pondering is added to the time the search is allowed
+*/
+#[derive(Default)]
+pub struct SharedSearch {
+    pub stop: AtomicBool,
+    pub abort: AtomicBool,
+    pub nodes: AtomicU64,
+    pub pondering: AtomicBool,
+    pub ponder_time_ms: AtomicU64,
+    pub params: SearchParams,
+}
+
+impl SharedSearch {
+    pub fn abort(&self) {
+        self.abort.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.abort.load(Ordering::Relaxed)
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Acquire)
+    }
+
+    // the opponent played the expected move, start is when the search began
+    pub fn ponderhit(&self, start: Instant) {
+        self.ponder_time_ms
+            .store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+        self.pondering.store(false, Ordering::Release);
+    }
+}
 
 /*
     Keep track of global information about the current search context
+
+    The principle variation is built up in a triangular table, see
+    https://www.chessprogramming.org/Triangular_PV-Table
+    Row ply holds the best line found from that ply, which is the best move followed by
+    the row below. The row for the root is the principle variation of the whole search
 */
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Search {
     pub killer_moves: KillerMoveArray, // the killer moves for this search
-    pub pv_moves: MoveArray,           // the principle variation for this search
-    pub cur_line: MoveArray,           // the current line being considered for this search
+    pub history: History,
+    pub pv_moves: MoveArray, // the principle variation from the last depth searched
+    pv_table: PvTable,
+    pv_length: [usize; MAX_DEPTH as usize],
+    // how many plies the line leading to each ply has been extended by
+    extensions: [u8; MAX_DEPTH as usize],
     pub nodes_searched: u32,
+    pub node_limit: u64,
+    pub thread_id: usize, // the main thread is 0, any others are helpers
+    pub params: SearchParams,
+    pub shared: Arc<SharedSearch>,
+    unshared_nodes: u64, // nodes not yet added to the shared count
+    ordering: OrderingStats,
+}
+
+/*
+    How often the move that caused a beta cutoff was ordered first, the better the
+    move ordering the fewer moves need to be searched before a cutoff
+*/
+#[derive(Clone, Copy, Default, Debug)]
+pub struct OrderingStats {
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    // sum of the position of every cutoff move, 1 is the first move
+    pub cutoff_move_total: u64,
+}
+
+impl OrderingStats {
+    pub fn first_move_rate(&self) -> f64 {
+        100.0 * self.first_move_cutoffs as f64 / self.cutoffs.max(1) as f64
+    }
+
+    pub fn average_cutoff_move(&self) -> f64 {
+        self.cutoff_move_total as f64 / self.cutoffs.max(1) as f64
+    }
 }
 
 impl Search {
-    pub fn new_search() -> Search {
+    // the history can be carried on from an earlier search of the same thread
+    pub fn new_search(shared: Arc<SharedSearch>, thread_id: usize, history: History) -> Search {
         Search {
             killer_moves: [[None; KILLER_MOVE_PLY_SIZE]; MAX_DEPTH as usize],
+            history,
             pv_moves: [None; MAX_DEPTH as usize],
-            cur_line: [None; MAX_DEPTH as usize],
+            pv_table: [[None; MAX_DEPTH as usize]; MAX_DEPTH as usize],
+            pv_length: [0; MAX_DEPTH as usize],
+            extensions: [0; MAX_DEPTH as usize],
             nodes_searched: 0,
+            node_limit: u64::MAX,
+            thread_id,
+            params: shared.params,
+            shared,
+            unshared_nodes: 0,
+            ordering: OrderingStats::default(),
         }
     }
 
     pub fn node_searched(&mut self) {
         self.nodes_searched += 1;
+        self.unshared_nodes += 1;
+        if self.unshared_nodes == NODE_BATCH_SIZE {
+            self.flush_nodes();
+        }
+    }
+
+    // add any nodes this thread has searched to the shared count
+    pub fn flush_nodes(&mut self) {
+        self.shared
+            .nodes
+            .fetch_add(self.unshared_nodes, Ordering::Relaxed);
+        self.unshared_nodes = 0;
+    }
+
+    // nodes searched by every thread over every depth
+    pub fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.unshared_nodes
     }
 
-    pub fn insert_killer_move(&mut self, ply_from_root: i32, mov: &BoardState) {
+    pub fn is_main_thread(&self) -> bool {
+        self.thread_id == 0
+    }
+
+    /*
+        The search has used up its time or nodes and should return as soon as possible
+        Helper threads only stop once the main thread tells them to
+    */
+    pub fn should_stop(&self, start: Instant, time_to_move_ms: u128) -> bool {
+        if self.shared.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        if !self.is_main_thread() {
+            return false;
+        }
+        if self.shared.is_aborted() {
+            self.stop();
+            return true;
+        }
+        if self.shared.is_pondering() {
+            return false;
+        }
+        let ponder_time_ms = self.shared.ponder_time_ms.load(Ordering::Relaxed) as u128;
+        if self.total_nodes() >= self.node_limit
+            || out_of_time(start, time_to_move_ms.saturating_add(ponder_time_ms))
+        {
+            self.stop();
+            return true;
+        }
+        false
+    }
+
+    pub fn stop(&self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn insert_killer_move(&mut self, ply_from_root: i32, mov: Move) {
         let ply = ply_from_root as usize;
-        if self.killer_moves[ply].contains(&mov.last_move) {
+        if self.killer_moves[ply].contains(&Some(mov)) {
             return;
         }
 
         for i in 0..(KILLER_MOVE_PLY_SIZE - 1) {
             self.killer_moves[ply][i + 1] = self.killer_moves[ply][i];
         }
-        self.killer_moves[ply][0] = mov.last_move;
+        self.killer_moves[ply][0] = Some(mov);
+    }
+
+    // the move at index move_index, counting from 0, of the ordered moves caused a beta cutoff
+    pub fn record_cutoff(&mut self, move_index: usize) {
+        self.ordering.cutoffs += 1;
+        self.ordering.cutoff_move_total += move_index as u64 + 1;
+        if move_index == 0 {
+            self.ordering.first_move_cutoffs += 1;
+        }
+    }
+
+    pub fn ordering_stats(&self) -> OrderingStats {
+        self.ordering
+    }
+
+    // every node starts without a line, so nothing is left over from an earlier search
+    pub fn clear_pv(&mut self, ply_from_root: i32) {
+        self.pv_length[ply_from_root as usize] = 0;
+    }
+
+    // mov is the new best move at this ply, followed by the best line found after it
+    pub fn update_pv(&mut self, ply_from_root: i32, mov: Move) {
+        let ply = ply_from_root as usize;
+        let child_length = if ply + 1 < MAX_DEPTH as usize {
+            self.pv_length[ply + 1]
+        } else {
+            0
+        };
+        let (rows, child_rows) = self.pv_table.split_at_mut(ply + 1);
+        let row = &mut rows[ply];
+        row[0] = Some(mov);
+        if child_length > 0 {
+            row[1..=child_length].copy_from_slice(&child_rows[0][..child_length]);
+        }
+        self.pv_length[ply] = child_length + 1;
     }
 
-    pub fn insert_into_cur_line(&mut self, ply_from_root: i32, mov: &BoardState) {
-        self.cur_line[ply_from_root as usize] = mov.last_move;
+    // the best line found from the root, with None after the last move
+    pub fn principle_variation(&self) -> MoveArray {
+        let mut pv = [None; MAX_DEPTH as usize];
+        let length = self.pv_length[0];
+        pv[..length].copy_from_slice(&self.pv_table[0][..length]);
+        pv
     }
 
-    pub fn set_principle_variation(&mut self) {
-        self.pv_moves.clone_from_slice(&self.cur_line);
+    /*
+        Grant as much of the extension wanted for the move about to be searched from this ply
+        as the line has left, and remember the total for the plies after it. Must be called
+        before searching any child node, even without an extension
+
+        A line is never extended past MAX_LINE_EXTENSIONS, or to a depth that can't fit in MAX_DEPTH
+    */
+    pub fn extend_line(&mut self, ply_from_root: i32, depth: u8, extension: u8) -> u8 {
+        let ply = ply_from_root as usize;
+        let used = self.extensions[ply];
+        let room = (MAX_DEPTH as usize).saturating_sub(ply + depth as usize + 1) as u8;
+        let granted = extension.min(MAX_LINE_EXTENSIONS - used).min(room);
+        if ply + 1 < MAX_DEPTH as usize {
+            self.extensions[ply + 1] = used + granted;
+        }
+        granted
     }
 
     // reset the required data to search the next depth
     pub fn reset_search(&mut self) {
         self.nodes_searched = 0;
-        self.cur_line = [None; MAX_DEPTH as usize];
+        self.pv_length = [0; MAX_DEPTH as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::*;
+    use std::time::Duration;
+
+    #[test]
+    fn time_to_move_from_limits() {
+        assert_eq!(SearchLimits::default().time_to_move_ms(White), u128::MAX);
+        assert_eq!(SearchLimits::time(500).time_to_move_ms(White), 500);
+
+        let clock = GameTime {
+            wtime: 30_100,
+            btime: 3_100,
+            winc: 0,
+            binc: 0,
+            movestogo: Some(10),
+        };
+        let mut limits = SearchLimits {
+            clock: Some(clock),
+            ..SearchLimits::default()
+        };
+        assert_eq!(limits.time_to_move_ms(White), 2400);
+        assert_eq!(limits.time_to_move_ms(Black), 240);
+        // the shorter of the clock and movetime is used
+        limits.movetime = Some(1000);
+        assert_eq!(limits.time_to_move_ms(White), 1000);
+        assert_eq!(limits.time_to_move_ms(Black), 240);
+        limits.infinite = true;
+        assert_eq!(limits.time_to_move_ms(Black), u128::MAX);
+    }
+
+    #[test]
+    fn mate_limit() {
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::default()
+        };
+        assert!(limits.is_mate_found(Some(1)));
+        assert!(limits.is_mate_found(Some(3)));
+        assert!(!limits.is_mate_found(Some(5)));
+        assert!(!limits.is_mate_found(None));
+        assert!(!SearchLimits::default().is_mate_found(Some(1)));
+    }
+
+    #[test]
+    fn stop_at_node_limit() {
+        let start = Instant::now();
+        let mut search_info =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        search_info.node_limit = 3;
+        search_info.node_searched();
+        search_info.node_searched();
+        assert!(!search_info.should_stop(start, u128::MAX));
+        // node counts carry over between depths
+        search_info.reset_search();
+        search_info.node_searched();
+        assert!(search_info.should_stop(start, u128::MAX));
+    }
+
+    #[test]
+    fn pv_built_from_best_child_line() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let moves = generate_moves(&board, MoveGenerationMode::AllMoves);
+        let mut search_info =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        search_info.clear_pv(0);
+        search_info.clear_pv(1);
+        search_info.clear_pv(2);
+        search_info.update_pv(2, moves[2]);
+        search_info.update_pv(1, moves[1]);
+        search_info.update_pv(0, moves[0]);
+        let pv = search_info.principle_variation();
+        assert_eq!(
+            &pv[..4],
+            &[Some(moves[0]), Some(moves[1]), Some(moves[2]), None]
+        );
+
+        // a better move at ply 1 with no line after it cuts the pv short
+        search_info.clear_pv(2);
+        search_info.update_pv(1, moves[3]);
+        search_info.update_pv(0, moves[0]);
+        let pv = search_info.principle_variation();
+        assert_eq!(&pv[..3], &[Some(moves[0]), Some(moves[3]), None]);
+
+        search_info.reset_search();
+        assert_eq!(search_info.principle_variation()[0], None);
+    }
+
+    #[test]
+    fn pv_fits_at_max_depth() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let mov = generate_moves(&board, MoveGenerationMode::AllMoves)[0];
+        let mut search_info =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        for ply in (0..MAX_DEPTH as i32).rev() {
+            search_info.update_pv(ply, mov);
+        }
+        assert!(search_info
+            .principle_variation()
+            .iter()
+            .all(|&m| m == Some(mov)));
+    }
+
+    #[test]
+    fn stop_at_time_limit() {
+        let search_info =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        assert!(!search_info.should_stop(Instant::now(), 60_000));
+        assert!(search_info.should_stop(Instant::now(), 0));
+    }
+
+    #[test]
+    fn helper_threads_stopped_by_main_thread() {
+        let shared = Arc::new(SharedSearch::default());
+        let main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        let mut helper = Search::new_search(Arc::clone(&shared), 1, History::default());
+        helper.node_limit = 0;
+        // helpers leave the limits to the main thread
+        assert!(!helper.should_stop(Instant::now(), 0));
+
+        assert!(main_thread.should_stop(Instant::now(), 0));
+        assert!(helper.should_stop(Instant::now(), 60_000));
+    }
+
+    #[test]
+    fn aborted_search_stopped() {
+        let shared = Arc::new(SharedSearch::default());
+        let main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        let helper = Search::new_search(Arc::clone(&shared), 1, History::default());
+        assert!(!main_thread.should_stop(Instant::now(), u128::MAX));
+        shared.abort();
+        assert!(!helper.should_stop(Instant::now(), u128::MAX));
+        assert!(main_thread.should_stop(Instant::now(), u128::MAX));
+        assert!(helper.should_stop(Instant::now(), u128::MAX));
+    }
+
+    #[test]
+    fn extensions_capped_per_line() {
+        let mut search =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        for ply in 0..MAX_LINE_EXTENSIONS as i32 {
+            assert_eq!(search.extend_line(ply, 4, 1), 1);
+        }
+        let capped = MAX_LINE_EXTENSIONS as i32;
+        assert_eq!(search.extend_line(capped, 4, 1), 0);
+
+        // a sibling line only carries the extensions made before it split off
+        assert_eq!(search.extend_line(2, 4, 0), 0);
+        assert_eq!(search.extend_line(3, 4, 1), 1);
+
+        // there is no room left for a deeper search near the end of the ply tables
+        let mut search =
+            Search::new_search(Arc::new(SharedSearch::default()), 0, History::default());
+        assert_eq!(search.extend_line(MAX_DEPTH as i32 - 5, 4, 1), 0);
+        assert_eq!(search.extend_line(MAX_DEPTH as i32 - 6, 4, 1), 1);
+    }
+
+    #[test]
+    fn search_params_set_by_name() {
+        let mut params = SearchParams::default();
+        assert_eq!(params.set("LmrBase", 100), Ok(()));
+        assert_eq!(params.set("lmpdepthfactor", 4), Ok(()));
+        assert_eq!(params.lmr_base, 100);
+        assert_eq!(params.lmp_depth_factor, 4);
+        assert!(params.set("LmrDivisor", 0).is_err());
+        assert!(params.set("Unknown", 1).is_err());
+        assert_eq!(params.lmr_divisor, SearchParams::default().lmr_divisor);
+        for (name, min, max, _) in SearchParams::OPTIONS {
+            assert_eq!(params.set(name, min), Ok(()));
+            assert_eq!(params.set(name, max), Ok(()));
+        }
+    }
+
+    #[test]
+    fn search_params_set_their_own_field() {
+        for (name, _, max, field) in SearchParams::OPTIONS {
+            let mut params = SearchParams::default();
+            let mut expected = SearchParams::default();
+            *field(&mut expected) = max;
+            assert_eq!(params.set(name, max), Ok(()));
+            assert_eq!(params, expected, "{}", name);
+        }
+        // no two options share a field
+        for (i, (name, .., field)) in SearchParams::OPTIONS.iter().enumerate() {
+            let mut params = SearchParams::default();
+            *field(&mut params) = -1;
+            for (j, (other, .., other_field)) in SearchParams::OPTIONS.iter().enumerate() {
+                if i != j {
+                    assert_ne!(*other_field(&mut params), -1, "{} and {}", name, other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn late_moves_reduced() {
+        let params = SearchParams::default();
+        assert_eq!(params.late_move_reduction(2, 30), 0);
+        assert_eq!(params.late_move_reduction(8, 1), 0);
+        assert!(params.late_move_reduction(8, 10) > 0);
+        assert!(params.late_move_reduction(12, 30) >= params.late_move_reduction(6, 30));
+        assert!(params.late_move_reduction(12, 30) >= params.late_move_reduction(12, 5));
+        // at least one ply is left to search
+        for depth in 2..MAX_DEPTH {
+            assert!(params.late_move_reduction(depth, 200) <= depth - 2);
+        }
+    }
+
+    #[test]
+    fn late_moves_pruned_at_shallow_depths() {
+        let params = SearchParams::default();
+        assert_eq!(params.late_move_count(1), Some(5));
+        assert_eq!(params.late_move_count(3), Some(21));
+        assert_eq!(params.late_move_count(4), None);
+    }
+
+    #[test]
+    fn pruning_margins_grow_with_depth() {
+        let mut params = SearchParams::default();
+        assert_eq!(params.reverse_futility_margin(1), Some(80));
+        assert_eq!(params.reverse_futility_margin(6), Some(480));
+        assert_eq!(params.reverse_futility_margin(7), None);
+        assert_eq!(params.futility_margin(2), Some(300));
+        assert_eq!(params.futility_margin(4), None);
+        assert_eq!(params.razor_margin(1), Some(400));
+        assert_eq!(params.razor_margin(4), None);
+
+        // a max depth of 0 turns the pruning off, depth 0 is always quiescence
+        params.set("RfpMaxDepth", 0).unwrap();
+        params.set("FutilityMaxDepth", 0).unwrap();
+        params.set("RazorMaxDepth", 0).unwrap();
+        assert_eq!(params.reverse_futility_margin(1), None);
+        assert_eq!(params.futility_margin(1), None);
+        assert_eq!(params.razor_margin(1), None);
+    }
+
+    #[test]
+    fn limits_ignored_while_pondering() {
+        let shared = Arc::new(SharedSearch::default());
+        shared.pondering.store(true, Ordering::Relaxed);
+        let mut main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        main_thread.node_limit = 0;
+        let start = Instant::now() - Duration::from_millis(100);
+        assert!(!main_thread.should_stop(start, 0));
+
+        shared.ponderhit(start);
+        assert!(!shared.is_pondering());
+        assert!(main_thread.should_stop(start, 0));
+    }
+
+    #[test]
+    fn ponder_time_added_to_time_to_move() {
+        let shared = Arc::new(SharedSearch::default());
+        shared.pondering.store(true, Ordering::Relaxed);
+        let main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        let start = Instant::now() - Duration::from_millis(100);
+        assert!(!main_thread.should_stop(start, 50));
+
+        // the 100ms spent pondering don't count against the 500ms to move
+        shared.ponderhit(start);
+        assert!(!main_thread.should_stop(start, 500));
+
+        let shared = Arc::new(SharedSearch::default());
+        let main_thread = Search::new_search(shared, 0, History::default());
+        assert!(main_thread.should_stop(start, 50));
+    }
+
+    #[test]
+    fn ponder_search_still_aborted() {
+        let shared = Arc::new(SharedSearch::default());
+        shared.pondering.store(true, Ordering::Relaxed);
+        let main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        shared.abort();
+        assert!(main_thread.should_stop(Instant::now(), u128::MAX));
+    }
+
+    #[test]
+    fn nodes_shared_between_threads() {
+        let shared = Arc::new(SharedSearch::default());
+        let mut main_thread = Search::new_search(Arc::clone(&shared), 0, History::default());
+        let mut helper = Search::new_search(Arc::clone(&shared), 1, History::default());
+        for _ in 0..NODE_BATCH_SIZE + 5 {
+            helper.node_searched();
+        }
+        main_thread.node_searched();
+        assert_eq!(main_thread.total_nodes(), NODE_BATCH_SIZE + 1);
+        helper.flush_nodes();
+        assert_eq!(main_thread.total_nodes(), NODE_BATCH_SIZE + 6);
     }
 }
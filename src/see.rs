@@ -0,0 +1,261 @@
+pub use crate::board::*;
+pub use crate::board::{PieceColor::*, PieceKind::*};
+
+const KNIGHT_OFFSETS: [(isize, isize); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ORTHOGONALS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// piece values used when trading pieces, the king is worth more than anything it can capture
+fn see_value(kind: PieceKind) -> i32 {
+    match kind {
+        Pawn => 100,
+        Knight => 300,
+        Bishop => 300,
+        Rook => 500,
+        Queen => 900,
+        King => 20000,
+    }
+}
+
+impl BoardState {
+    /*
+        Static Exchange Evaluation, see https://www.chessprogramming.org/Static_Exchange_Evaluation
+
+        The material this side wins, or loses if negative, once both sides have finished
+        capturing on the target square of the move. Each side always recaptures with its
+        least valuable piece and may stop whenever carrying on would lose more material
+
+        Pieces behind an attacker on the same line join in once it has captured, pins
+        are not considered
+    */
+    pub fn see(&self, mov: Move) -> i32 {
+        let start = mov.start();
+        let target = mov.end();
+        let mut board = self.board;
+
+        let mut on_target = match board[start.0][start.1] {
+            Square::Full(piece) => see_value(piece.kind),
+            _ => return 0,
+        };
+        let mut gain = [0; 32];
+        gain[0] = match board[target.0][target.1] {
+            Square::Full(piece) => see_value(piece.kind),
+            _ if mov.is_en_passant() => {
+                board[start.0][target.1] = Square::Empty;
+                see_value(Pawn)
+            }
+            _ => 0,
+        };
+        if let Some(kind) = mov.promotion_kind() {
+            gain[0] += see_value(kind) - see_value(Pawn);
+            on_target = see_value(kind);
+        }
+        board[start.0][start.1] = Square::Empty;
+
+        let mut color = self.to_move.opposite();
+        let mut depth = 0;
+        while let Some(attacker) = least_valuable_attacker(&board, target, color) {
+            depth += 1;
+            // capturing the piece that just moved here, against everything won so far
+            gain[depth] = on_target - gain[depth - 1];
+            // this side is already worse off whether it captures or not, so it stops here
+            if gain[depth].max(-gain[depth - 1]) < 0 {
+                depth -= 1;
+                break;
+            }
+            if depth == gain.len() - 1 {
+                break;
+            }
+            let Square::Full(piece) = board[attacker.0][attacker.1] else {
+                break;
+            };
+            on_target = see_value(piece.kind);
+            board[attacker.0][attacker.1] = Square::Empty;
+            color = color.opposite();
+        }
+
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+}
+
+// the square of the cheapest piece of this color that attacks the target
+fn least_valuable_attacker(
+    board: &[[Square; 12]; 12],
+    target: Point,
+    color: PieceColor,
+) -> Option<Point> {
+    let piece_at = |(row, col): (isize, isize)| {
+        let point = Point(
+            (target.0 as isize + row) as usize,
+            (target.1 as isize + col) as usize,
+        );
+        match board[point.0][point.1] {
+            Square::Full(piece) if piece.color == color => Some((point, piece.kind)),
+            _ => None,
+        }
+    };
+
+    // pawns capture towards the other side of the board, white moves up to row 2
+    let pawn_row = if color == White { 1 } else { -1 };
+    let pawn = [(pawn_row, -1), (pawn_row, 1)]
+        .into_iter()
+        .filter_map(piece_at)
+        .find(|&(_, kind)| kind == Pawn);
+    if let Some((point, _)) = pawn {
+        return Some(point);
+    }
+    let knight = KNIGHT_OFFSETS
+        .into_iter()
+        .filter_map(piece_at)
+        .find(|&(_, kind)| kind == Knight);
+    if let Some((point, _)) = knight {
+        return Some(point);
+    }
+
+    // the first piece along each line, which is only an attacker if it moves that way
+    let diagonal = first_pieces(board, target, &DIAGONALS, color);
+    let orthogonal = first_pieces(board, target, &ORTHOGONALS, color);
+    let slider = [(&diagonal, Bishop), (&orthogonal, Rook)]
+        .into_iter()
+        .chain([(&diagonal, Queen), (&orthogonal, Queen)])
+        .find_map(|(lines, kind)| lines.iter().find(|&&(_, found)| found == kind));
+    if let Some(&(point, _)) = slider {
+        return Some(point);
+    }
+
+    KING_OFFSETS
+        .into_iter()
+        .filter_map(piece_at)
+        .find(|&(_, kind)| kind == King)
+        .map(|(point, _)| point)
+}
+
+// the nearest piece of this color in each direction, if nothing is in the way
+fn first_pieces(
+    board: &[[Square; 12]; 12],
+    target: Point,
+    directions: &[(isize, isize)],
+    color: PieceColor,
+) -> Vec<(Point, PieceKind)> {
+    let mut pieces = Vec::new();
+    for &(row, col) in directions {
+        let mut point = target;
+        loop {
+            point = Point(
+                (point.0 as isize + row) as usize,
+                (point.1 as isize + col) as usize,
+            );
+            match board[point.0][point.1] {
+                Square::Empty => continue,
+                Square::Full(piece) if piece.color == color => {
+                    pieces.push((point, piece.kind));
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::*;
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let board = BoardState::from_fen(fen).unwrap();
+        let mov = generate_moves(&board, MoveGenerationMode::AllMoves)
+            .into_iter()
+            .find(|mov| mov.to_string() == uci)
+            .unwrap();
+        board.see(mov)
+    }
+
+    #[test]
+    fn undefended_capture_wins_piece() {
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        assert_eq!(see("4k3/8/8/3r4/8/8/8/3QK3 w - - 0 1", "d1d5"), 500);
+    }
+
+    #[test]
+    fn defended_capture_loses_material() {
+        // Qxd5 exd5
+        assert_eq!(see("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", "d1d5"), -800);
+        // Nxe5 Nxe5 Rxe5 Bxe5 Qxe5 Qxe5
+        assert_eq!(
+            see(
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "d3e5"
+            ),
+            -200
+        );
+    }
+
+    #[test]
+    fn even_trades() {
+        assert_eq!(see("4k3/8/4p3/3n4/8/4N3/8/4K3 w - - 0 1", "e3d5"), 0);
+        assert_eq!(
+            see(
+                "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+                "e4d5"
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn x_ray_attackers_join_in() {
+        // the rook behind the first one recaptures, so the pawn is won
+        assert_eq!(see("4k3/4r3/8/8/4p3/8/4R3/4R2K w - - 0 1", "e2e4"), 100);
+        // without the second rook the exchange loses the rook
+        assert_eq!(see("4k3/4r3/8/8/4p3/8/4R3/7K w - - 0 1", "e2e4"), -400);
+        // a queen behind a bishop
+        assert_eq!(see("4k3/8/2p5/3n4/8/5B2/6Q1/K7 w - - 0 1", "f3d5"), 100);
+    }
+
+    #[test]
+    fn king_only_recaptures_when_safe() {
+        // the king can't take back on a square the rook defends
+        assert_eq!(see("8/8/8/3k4/8/3r4/3R4/3RK3 w - - 0 1", "d2d3"), 500);
+        assert_eq!(see("8/8/8/8/3k4/3r4/3R4/4K3 w - - 0 1", "d2d3"), 0);
+    }
+
+    #[test]
+    fn special_moves() {
+        // en passant
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        // promoting wins the difference between the queen and the pawn
+        assert_eq!(see("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), 500 + 800);
+        // a quiet move onto a square a pawn attacks
+        assert_eq!(see("4k3/8/8/4p3/8/5N2/8/4K3 w - - 0 1", "f3d4"), -300);
+        assert_eq!(see(DEFAULT_FEN_STRING, "g1f3"), 0);
+    }
+}
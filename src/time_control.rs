@@ -5,6 +5,7 @@ const GAME_LENGTH: u32 = 30; // moves
 const MAX_USAGE: f64 = 0.8; // percentage
 const NO_TIME: u128 = 0;
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct GameTime {
     // all time is in ms unless otherwise specified
     pub wtime: i128,
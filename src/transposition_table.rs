@@ -0,0 +1,308 @@
+use crate::board::Move;
+use crate::engine::MATE_SCORE;
+use crate::search::MAX_DEPTH;
+use crate::zobrist::ZobristKey;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_SIZE_MB: usize = 16;
+pub const MIN_HASH_SIZE_MB: usize = 1;
+pub const MAX_HASH_SIZE_MB: usize = 1024;
+
+// number of entries that share a single index, a bucket fits exactly into a 64 byte cache line
+const BUCKET_SIZE: usize = 4;
+const BYTES_PER_BUCKET: usize = BUCKET_SIZE * std::mem::size_of::<Entry>();
+
+// any score outside this window is a mate score and depends on the distance from the root
+const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_DEPTH as i32;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    // the score is the exact value of the position
+    Exact,
+    // the search failed high, the real score is at least this value
+    Lower,
+    // the search failed low, the real score is at most this value
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TableEntry {
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: i32,
+    pub best_move: Option<Move>,
+}
+
+/*
+    A single slot in the table. The key is stored xor'ed with the data so a torn
+    write from another thread is detected as a miss rather than returning garbage,
+    see https://www.chessprogramming.org/Shared_Hash_Table#Lockless
+*/
+struct Entry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/*
+    Fixed size table mapping zobrist keys to previous search results
+
+    Layout of the packed data
+    bits  0..32 score
+    bits 32..40 depth
+    bits 40..42 bound
+    bits 42..58 best move, 0 means no move
+    bits 61..64 generation the entry was written in
+*/
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+    buckets: usize,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let size_mb = size_mb.clamp(MIN_HASH_SIZE_MB, MAX_HASH_SIZE_MB);
+        let buckets = size_mb * 1024 * 1024 / BYTES_PER_BUCKET;
+        let mut entries = Vec::with_capacity(buckets * BUCKET_SIZE);
+        for _ in 0..buckets * BUCKET_SIZE {
+            entries.push(Entry {
+                key: AtomicU64::new(0),
+                data: AtomicU64::new(0),
+            });
+        }
+
+        TranspositionTable {
+            entries,
+            buckets,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    // the memory used by the table, which can be less than the size asked for
+    pub fn size_mb(&self) -> usize {
+        self.buckets * BYTES_PER_BUCKET / (1024 * 1024)
+    }
+
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            entry.key.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // Called once per search so entries from older searches are replaced first
+    pub fn new_search(&self) {
+        let generation = (self.generation.load(Ordering::Relaxed) + 1) & 0b111;
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    /*
+        Look up the given key, scores are adjusted so that mate scores are relative
+        to the current ply rather than the ply they were stored at
+    */
+    pub fn probe(&self, key: ZobristKey, ply_from_root: i32) -> Option<TableEntry> {
+        for entry in self.bucket(key) {
+            let data = entry.data.load(Ordering::Relaxed);
+            if data != 0 && entry.key.load(Ordering::Relaxed) ^ data == key {
+                let mut table_entry = unpack(data);
+                table_entry.score = score_from_table(table_entry.score, ply_from_root);
+                return Some(table_entry);
+            }
+        }
+        None
+    }
+
+    pub fn store(
+        &self,
+        key: ZobristKey,
+        depth: u8,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+        ply_from_root: i32,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        // prefer the slot already holding this position, otherwise replace the least useful entry
+        let mut replace = &bucket[0];
+        let mut replace_value = i32::MAX;
+        for entry in bucket {
+            let data = entry.data.load(Ordering::Relaxed);
+            if data == 0 || entry.key.load(Ordering::Relaxed) ^ data == key {
+                replace = entry;
+                break;
+            }
+            let age = (generation.wrapping_sub(entry_generation(data)) & 0b111) as i32;
+            let value = unpack(data).depth as i32 - 8 * age;
+            if value < replace_value {
+                replace_value = value;
+                replace = entry;
+            }
+        }
+
+        let mut table_entry = TableEntry {
+            depth,
+            bound,
+            score: score_to_table(score, ply_from_root),
+            best_move,
+        };
+
+        // keep the old best move around if we don't have a better suggestion
+        let old_data = replace.data.load(Ordering::Relaxed);
+        if best_move.is_none() && replace.key.load(Ordering::Relaxed) ^ old_data == key {
+            table_entry.best_move = unpack(old_data).best_move;
+        }
+
+        let data = pack(&table_entry, generation);
+        replace.key.store(key ^ data, Ordering::Relaxed);
+        replace.data.store(data, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: ZobristKey) -> &[Entry] {
+        let index = (key % self.buckets as u64) as usize * BUCKET_SIZE;
+        &self.entries[index..index + BUCKET_SIZE]
+    }
+}
+
+/*
+    Mate scores are stored as the distance from the current position rather than
+    the root, since the same position can be reached at different plies
+*/
+fn score_to_table(score: i32, ply_from_root: i32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply_from_root
+    } else if score <= -MATE_THRESHOLD {
+        score - ply_from_root
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply_from_root: i32) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply_from_root
+    } else if score <= -MATE_THRESHOLD {
+        score + ply_from_root
+    } else {
+        score
+    }
+}
+
+fn pack(entry: &TableEntry, generation: u8) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let best_move = entry.best_move.map_or(0, |mov| mov.to_bits() as u64);
+
+    entry.score as u32 as u64
+        | (entry.depth as u64) << 32
+        | bound << 40
+        | best_move << 42
+        | (generation as u64) << 61
+}
+
+fn unpack(data: u64) -> TableEntry {
+    let bound = match (data >> 40) & 0b11 {
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => Bound::Exact,
+    };
+    // a8a8 is never a legal move so it can safely represent the lack of a move
+    let mov = (data >> 42) as u16;
+    let best_move = if mov == 0 {
+        None
+    } else {
+        Some(Move::from_bits(mov))
+    };
+
+    TableEntry {
+        depth: (data >> 32) as u8,
+        bound,
+        score: data as u32 as i32,
+        best_move,
+    }
+}
+
+fn entry_generation(data: u64) -> u8 {
+    (data >> 61) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{PieceKind::*, Point};
+
+    const KEY: ZobristKey = 0xDEAD_BEEF_1234_5678;
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let mov = Some(Move::double_pawn_push(Point(8, 6), Point(6, 6)));
+        tt.store(KEY, 5, Bound::Lower, -123, mov, 3);
+        let entry = tt.probe(KEY, 3).unwrap();
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.score, -123);
+        assert_eq!(entry.best_move, mov);
+        assert!(tt.probe(KEY + 1, 3).is_none());
+    }
+
+    #[test]
+    fn store_promotion() {
+        let tt = TranspositionTable::new(1);
+        let mov = Some(Move::promotion(Point(3, 9), Point(2, 9), Knight, false));
+        tt.store(KEY, 1, Bound::Exact, 800, mov, 0);
+        let entry = tt.probe(KEY, 0).unwrap();
+        assert_eq!(entry.best_move, mov);
+        assert_eq!(entry.best_move.unwrap().promotion_kind(), Some(Knight));
+    }
+
+    #[test]
+    fn mate_scores_relative_to_ply() {
+        let tt = TranspositionTable::new(1);
+        // mate found 5 plies from the root, stored while 2 plies from the root
+        tt.store(KEY, 3, Bound::Exact, MATE_SCORE - 5, None, 2);
+        // same position reached 4 plies from the root is 2 plies further from the mate
+        assert_eq!(tt.probe(KEY, 4).unwrap().score, MATE_SCORE - 7);
+
+        tt.store(KEY, 3, Bound::Exact, -MATE_SCORE + 5, None, 2);
+        assert_eq!(tt.probe(KEY, 1).unwrap().score, -MATE_SCORE + 4);
+    }
+
+    #[test]
+    fn best_move_kept_when_none_provided() {
+        let tt = TranspositionTable::new(1);
+        let mov = Some(Move::quiet(Point(9, 3), Point(7, 4)));
+        tt.store(KEY, 2, Bound::Exact, 10, mov, 0);
+        tt.store(KEY, 4, Bound::Upper, 5, None, 0);
+        let entry = tt.probe(KEY, 0).unwrap();
+        assert_eq!(entry.depth, 4);
+        assert_eq!(entry.best_move, mov);
+    }
+
+    #[test]
+    fn shallow_entries_replaced_first() {
+        let tt = TranspositionTable::new(1);
+        let buckets = tt.buckets as u64;
+        // all of these keys map to the same bucket
+        for i in 0..BUCKET_SIZE as u64 {
+            tt.store(KEY + i * buckets, 10 + i as u8, Bound::Exact, 0, None, 0);
+        }
+        tt.store(KEY + 10 * buckets, 20, Bound::Exact, 0, None, 0);
+        assert!(tt.probe(KEY, 0).is_none());
+        assert!(tt.probe(KEY + buckets, 0).is_some());
+        assert!(tt.probe(KEY + 10 * buckets, 0).is_some());
+    }
+
+    #[test]
+    fn clear_table() {
+        let tt = TranspositionTable::new(1);
+        tt.store(KEY, 2, Bound::Exact, 10, None, 0);
+        tt.clear();
+        assert!(tt.probe(KEY, 0).is_none());
+    }
+}
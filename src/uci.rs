@@ -1,21 +1,28 @@
 pub use crate::board::*;
 use crate::draw_table::DrawTable;
 pub use crate::engine::*;
+use crate::history::History;
+use crate::mate_solver::MateResult;
+use crate::perft::perft_report;
+use crate::search::{
+    SearchParams, SharedSearch, DEFAULT_MULTI_PV, MAX_MULTI_PV, MAX_THREADS, MIN_MULTI_PV,
+    MIN_THREADS,
+};
 pub use crate::time_control::*;
+use crate::transposition_table::{
+    TranspositionTable, DEFAULT_HASH_SIZE_MB, MAX_HASH_SIZE_MB, MIN_HASH_SIZE_MB,
+};
 pub use crate::utils::*;
 use crate::zobrist::ZobristHasher;
 use log::{error, info};
 use std::io::{self, BufRead};
 use std::process;
-use std::sync::mpsc;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-const WHITE_KING_SIDE_CASTLE_STRING: &str = "e1g1";
-const WHITE_QUEEN_SIDE_CASTLE_STRING: &str = "e1c1";
-const BLACK_KING_SIDE_CASTLE_STRING: &str = "e8g8";
-const BLACK_QUEEN_SIDE_CASTLE_STRING: &str = "e8c8";
-
 pub fn play_game_uci() {
     let mut board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
     let buffer = read_from_gui();
@@ -31,25 +38,77 @@ pub fn play_game_uci() {
     ));
     send_to_gui(&format!("id author {}", env!("CARGO_PKG_AUTHORS")));
     send_to_gui("option name DebugLogLevel type combo default None var Info var None");
+    send_to_gui(&format!(
+        "option name Hash type spin default {} min {} max {}",
+        DEFAULT_HASH_SIZE_MB, MIN_HASH_SIZE_MB, MAX_HASH_SIZE_MB
+    ));
+    send_to_gui(&format!(
+        "option name Threads type spin default {} min {} max {}",
+        DEFAULT_THREADS, MIN_THREADS, MAX_THREADS
+    ));
+    send_to_gui(&format!(
+        "option name MultiPV type spin default {} min {} max {}",
+        DEFAULT_MULTI_PV, MIN_MULTI_PV, MAX_MULTI_PV
+    ));
+    // the GUI decides when to ponder, this only tells it that we can
+    send_to_gui("option name Ponder type check default false");
     send_to_gui("uciok");
 
     let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
     let mut draw_table = DrawTable::new();
-    loop {
-        let buffer = read_from_gui();
+    let mut transposition_table = Arc::new(TranspositionTable::new(DEFAULT_HASH_SIZE_MB));
+    let mut threads = DEFAULT_THREADS;
+    let mut multi_pv = DEFAULT_MULTI_PV;
+    let mut search_params = SearchParams::default();
+    let mut search_controller = SearchController::new();
+    let input = spawn_input_reader();
+    // the GUI closing our input is treated the same as quit
+    while let Ok(buffer) = input.recv() {
         let start = Instant::now();
         let commands: Vec<&str> = buffer.split(' ').collect();
 
         match commands[0] {
+            // answered straight away, even in the middle of a search
             "isready" => send_to_gui("readyok"),
-            "ucinewgame" => (), // we don't keep any internal state really so no need to reset anything here
+            "ucinewgame" => {
+                search_controller.new_game();
+                transposition_table.clear();
+            }
             "position" => {
+                search_controller.stop();
                 draw_table.clear();
                 board = play_out_position(&commands, &zobrist_hasher, &mut draw_table);
-                info!("{}", board.simple_board());
+                info!("{}{}", board.simple_board(), board.to_fen());
+            }
+            "go" if commands.get(1) == Some(&"perft") => {
+                search_controller.stop();
+                match commands.get(2).and_then(|depth| depth.parse().ok()) {
+                    Some(depth) => {
+                        for line in perft_report(&board, depth, &zobrist_hasher) {
+                            send_to_gui(&line);
+                        }
+                    }
+                    None => error!("Invalid perft depth: {}", buffer),
+                }
             }
             "go" => {
-                board = find_and_play_best_move(&commands, &mut board, start, &mut draw_table);
+                search_controller.go(SearchRequest {
+                    board: board.clone(),
+                    draw_table: draw_table.clone(),
+                    start,
+                    limits: SearchLimits {
+                        multi_pv,
+                        search_moves: parse_search_moves(&commands, &board),
+                        ..parse_go_command(&commands)
+                    },
+                    threads,
+                    params: search_params,
+                    transposition_table: Arc::clone(&transposition_table),
+                });
+            }
+            "ponderhit" => search_controller.ponderhit(),
+            "stop" => {
+                search_controller.stop();
             }
             "setoption" => {
                 if commands.contains(&"DebugLogLevel") && commands.contains(&"Info") {
@@ -58,85 +117,357 @@ pub fn play_game_uci() {
                     if simple_logging::log_to_file(log_name, log::LevelFilter::Info).is_err() {
                         panic!("Something went wrong when trying to set up logs");
                     };
+                } else if commands.contains(&"Hash") {
+                    match parse_option_value(&commands).and_then(|v| v.parse().ok()) {
+                        Some(size_mb) => {
+                            transposition_table = Arc::new(TranspositionTable::new(size_mb))
+                        }
+                        None => error!("Invalid hash size: {}", buffer),
+                    }
+                } else if commands.contains(&"Threads") {
+                    match parse_option_value(&commands).and_then(|v| v.parse::<usize>().ok()) {
+                        Some(count) => threads = count.clamp(MIN_THREADS, MAX_THREADS),
+                        None => error!("Invalid thread count: {}", buffer),
+                    }
+                } else if commands.contains(&"MultiPV") {
+                    match parse_option_value(&commands).and_then(|v| v.parse::<usize>().ok()) {
+                        Some(lines) => multi_pv = lines.clamp(MIN_MULTI_PV, MAX_MULTI_PV),
+                        None => error!("Invalid MultiPV: {}", buffer),
+                    }
+                } else if commands.contains(&"Ponder") {
+                    // nothing to set, go ponder is what starts pondering
+                    match parse_option_value(&commands) {
+                        Some("true") | Some("false") => {}
+                        _ => error!("Invalid Ponder value: {}", buffer),
+                    }
+                } else if let Some(name) = parse_option_name(&commands) {
+                    // the hidden options used for tuning the search
+                    match parse_option_value(&commands).and_then(|v| v.parse().ok()) {
+                        Some(value) => {
+                            if let Err(err) = search_params.set(&name, value) {
+                                error!("{}: {}", err, buffer);
+                            }
+                        }
+                        None => error!("Invalid option value: {}", buffer),
+                    }
                 }
             }
-            "quit" => process::exit(1),
+            "quit" => {
+                search_controller.stop();
+                return;
+            }
             _ => error!("Unrecognized command: {}", buffer),
         };
     }
+    // let a search that was already started finish before exiting
+    search_controller.finish();
 }
 
 /*
-    Finds an plays the best move and sends it to UCI
-    Returns the new board state with the best move played
+    Read commands from the GUI on their own thread, so they can still be received
+    while a search is running
 */
-fn find_and_play_best_move(
-    commands: &[&str],
-    board: &mut BoardState,
+fn spawn_input_reader() -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let buffer = match line {
+                Ok(line) => clean_input(&line),
+                Err(_) => break,
+            };
+            info!("ENGINE << {}", buffer);
+            if buffer.is_empty() {
+                continue;
+            }
+            if tx.send(buffer).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// Everything needed to run a search away from the UCI loop
+struct SearchRequest {
+    board: BoardState,
+    draw_table: DrawTable,
     start: Instant,
-    draw_table: &mut DrawTable,
-) -> BoardState {
-    let time_to_move_ms = parse_go_command(commands).calculate_time_slice(board.to_move);
-    let mut best_move = None;
+    limits: SearchLimits,
+    threads: usize,
+    params: SearchParams,
+    transposition_table: Arc<TranspositionTable>,
+}
 
-    let (tx, rx) = mpsc::channel();
-    let clone = board.clone();
-    let mut draw_clone = draw_table.clone();
-    thread::spawn(move || get_best_move(&clone, &mut draw_clone, start, time_to_move_ms, &tx));
-    // keep looking until we are out of time
-    // also add a guard to ensure we at least get a move from the search thread
-    while !out_of_time(start, time_to_move_ms) || best_move.is_none() {
-        if let Ok(b) = rx.try_recv() {
-            best_move = Some(b);
-        } else {
-            thread::sleep(Duration::from_millis(1));
+/*
+    Runs each search on a background thread so the UCI loop is free to respond to the GUI
+
+    Every go command is answered with exactly one bestmove. A search started with
+    go infinite will not send its bestmove until it is stopped, even if it finishes early
+    and the same goes for go ponder until ponderhit is received
+
+    The move ordering history of each search thread is kept from one search to the next,
+    only a new game starts them over
+*/
+struct SearchController {
+    shared: Arc<SharedSearch>,
+    start: Instant,
+    infinite: bool,
+    handle: Option<thread::JoinHandle<Option<Move>>>,
+    histories: Arc<Mutex<Vec<History>>>,
+}
+
+impl SearchController {
+    fn new() -> SearchController {
+        SearchController {
+            shared: Arc::new(SharedSearch::default()),
+            start: Instant::now(),
+            infinite: false,
+            handle: None,
+            histories: Arc::new(Mutex::new(Vec::new())),
         }
     }
-    let board = best_move.unwrap();
-    send_best_move_to_gui(&board);
-    info!("{}", board.simple_board());
-    board
+
+    fn go(&mut self, request: SearchRequest) {
+        // a search that is still running must send its bestmove before the next one starts
+        self.stop();
+
+        let shared = Arc::new(SharedSearch {
+            params: request.params,
+            ..SharedSearch::default()
+        });
+        // set before the search starts so an early ponderhit isn't lost
+        shared
+            .pondering
+            .store(request.limits.ponder, Ordering::Release);
+        self.shared = Arc::clone(&shared);
+        self.start = request.start;
+        self.infinite = request.limits.infinite;
+        let histories = Arc::clone(&self.histories);
+        self.handle = Some(thread::spawn(move || {
+            let SearchRequest {
+                board,
+                mut draw_table,
+                start,
+                mut limits,
+                threads,
+                transposition_table,
+                ..
+            } = request;
+            let (tx, rx) = mpsc::channel();
+            // try to prove a mate first, the search is only needed when there isn't one
+            let mate = limits.mate.map(|moves| {
+                let result = find_forced_mate(
+                    &board,
+                    start,
+                    &limits,
+                    moves,
+                    transposition_table.size_mb(),
+                    &shared,
+                    &tx,
+                );
+                if result == MateResult::NoMate {
+                    // there is no mate to find, so searching any deeper won't help
+                    limits.depth = limits.depth.min(moves.saturating_mul(2));
+                }
+                result
+            });
+            if !matches!(mate, Some(MateResult::Mate(_))) {
+                get_best_move(
+                    &board,
+                    &mut draw_table,
+                    start,
+                    &limits,
+                    threads,
+                    &shared,
+                    &transposition_table,
+                    &mut histories.lock().unwrap(),
+                    &tx,
+                );
+            }
+            while (limits.infinite || shared.is_pondering()) && !shared.is_aborted() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            // the last result sent by the search has the best move found
+            let result = rx.try_iter().last();
+            send_best_move_to_gui(result);
+            result.map(|result| result.best_move)
+        }));
+    }
+
+    // Forget the move ordering learned in the last game
+    fn new_game(&mut self) {
+        self.stop();
+        self.histories.lock().unwrap().clear();
+    }
+
+    // The opponent played the move we were pondering on, carry on with the normal time limits
+    fn ponderhit(&self) {
+        if self.shared.is_pondering() {
+            self.shared.ponderhit(self.start);
+        }
+    }
+
+    // Abort the current search and wait for its bestmove to be sent
+    fn stop(&mut self) -> Option<Move> {
+        self.shared.abort();
+        self.wait()
+    }
+
+    // Wait for the current search to end on its own, an infinite or ponder search is stopped
+    fn finish(&mut self) -> Option<Move> {
+        if self.infinite || self.shared.is_pondering() {
+            self.shared.abort();
+        }
+        self.wait()
+    }
+
+    fn wait(&mut self) -> Option<Move> {
+        self.handle
+            .take()
+            .and_then(|handle| handle.join().unwrap_or(None))
+    }
 }
 
-// parse the go command and get relevant info about the current game time
-fn parse_go_command(commands: &[&str]) -> GameTime {
-    let mut gt = GameTime {
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
+/*
+    Parse the limits out of a go command, values that are missing or can't be parsed
+    are logged and ignored rather than stopping the engine
+*/
+fn parse_go_command(commands: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut clock = GameTime {
         wtime: 0,
         btime: 0,
         winc: 0,
         binc: 0,
         movestogo: None,
     };
+    let mut has_clock = false;
 
-    let mut i = 0;
-    while i + 1 < commands.len() {
-        match commands[i] {
+    for (i, &command) in commands.iter().enumerate() {
+        match command {
             "wtime" => {
-                gt.wtime = commands[i + 1].parse().unwrap();
-                i += 1;
+                if let Some(wtime) = parse_go_value(commands, i) {
+                    clock.wtime = wtime;
+                    has_clock = true;
+                }
             }
             "btime" => {
-                gt.btime = commands[i + 1].parse().unwrap();
-                i += 1;
+                if let Some(btime) = parse_go_value(commands, i) {
+                    clock.btime = btime;
+                    has_clock = true;
+                }
             }
-            "binc" => {
-                gt.binc = commands[i + 1].parse().unwrap();
-                i += 1;
+            "winc" => clock.winc = parse_go_value(commands, i).unwrap_or(clock.winc),
+            "binc" => clock.binc = parse_go_value(commands, i).unwrap_or(clock.binc),
+            "movestogo" => {
+                clock.movestogo = parse_go_value(commands, i)
+                    .filter(|&movestogo| movestogo > 0)
+                    .or(clock.movestogo)
             }
-            "winc" => {
-                gt.winc = commands[i + 1].parse().unwrap();
-                i += 1;
+            "depth" => {
+                if let Some(depth) = parse_go_value::<u8>(commands, i) {
+                    limits.depth = depth.clamp(1, MAX_DEPTH - 1);
+                }
             }
-            "movestogo" => {
-                gt.movestogo = Some(commands[i + 1].parse().unwrap());
-                i += 1;
+            "nodes" => limits.nodes = parse_go_value(commands, i).unwrap_or(limits.nodes),
+            "movetime" => limits.movetime = parse_go_value(commands, i).or(limits.movetime),
+            "mate" => {
+                limits.mate = parse_go_value(commands, i)
+                    .filter(|&moves| moves > 0)
+                    .or(limits.mate)
             }
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
             _ => (),
         }
-        i += 1;
     }
 
-    gt
+    if has_clock {
+        limits.clock = Some(clock);
+    }
+    limits
+}
+
+/*
+    Find the root moves listed after searchmoves in a go command, the list ends at the
+    next keyword. Moves that aren't legal are reported to the GUI and left out
+*/
+fn parse_search_moves(commands: &[&str], board: &BoardState) -> Vec<Move> {
+    let start_index = match commands.iter().position(|&c| c == "searchmoves") {
+        Some(i) => i + 1,
+        None => return Vec::new(),
+    };
+    let legal_moves = generate_moves(board, MoveGenerationMode::AllMoves);
+    let mut search_moves = Vec::new();
+    for &command in commands[start_index..]
+        .iter()
+        .take_while(|&&c| !GO_KEYWORDS.contains(&c))
+    {
+        match legal_moves.iter().find(|mov| mov.to_string() == command) {
+            Some(&mov) => search_moves.push(mov),
+            None => send_to_gui(&format!(
+                "info string Ignoring searchmoves {}, it is not a legal move",
+                command
+            )),
+        }
+    }
+    if search_moves.is_empty() {
+        send_to_gui("info string No legal searchmoves given, searching every move");
+    }
+    search_moves
+}
+
+// the value following a keyword of the go command
+fn parse_go_value<T: FromStr>(commands: &[&str], i: usize) -> Option<T> {
+    let value = commands.get(i + 1).and_then(|value| value.parse().ok());
+    if value.is_none() {
+        error!(
+            "Invalid value for {} in: {}",
+            commands[i],
+            commands.join(" ")
+        );
+    }
+    value
+}
+
+/*
+    Get the name out of a "setoption name <id> value <x>" command, which may contain spaces
+*/
+fn parse_option_name(commands: &[&str]) -> Option<String> {
+    let name_index = commands.iter().position(|c| *c == "name")?;
+    let value_index = commands
+        .iter()
+        .position(|c| *c == "value")
+        .unwrap_or(commands.len());
+    if value_index <= name_index + 1 {
+        return None;
+    }
+    Some(commands[name_index + 1..value_index].join(" "))
+}
+
+/*
+    Get the value out of a "setoption name <id> value <x>" command
+*/
+fn parse_option_value<'a>(commands: &[&'a str]) -> Option<&'a str> {
+    let value_index = commands.iter().position(|c| *c == "value")?;
+    commands.get(value_index + 1).copied()
 }
 
 /*
@@ -190,134 +521,28 @@ fn play_out_position(
     Play the opponents move on the board
 */
 fn make_move(board: &mut BoardState, player_move: &str, zobrist_hasher: &ZobristHasher) {
-    let start_pair: Point = (player_move[0..2]).parse().unwrap();
-    let end_pair: Point = (player_move[2..4]).parse().unwrap();
-    board.unset_pawn_double_move(zobrist_hasher);
-
-    if let Square::Full(piece) = board.board[start_pair.0][start_pair.1] {
-        // update king location
-        if piece.kind == King {
-            if piece.color == White {
-                board.white_king_location = end_pair;
-                board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-                board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-            } else {
-                board.black_king_location = end_pair;
-                board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-                board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-            }
-        } else if piece.kind == Pawn {
-            if (start_pair.0 as i8 - end_pair.0 as i8).abs() == 2 {
-                // pawn made a double move, record space behind pawn for en passant
-                let target = match piece.color {
-                    White => Point(start_pair.0 - 1, start_pair.1),
-                    Black => Point(start_pair.0 + 1, start_pair.1),
-                };
-                board.zobrist_key ^= zobrist_hasher.get_val_for_en_passant(target.1);
-                board.pawn_double_move = Some(target);
-            }
-            // check for en passant captures
-            // if a pawn moves diagonally and no capture is made, it must be an en passant capture
-            if start_pair.1 != end_pair.1 && board.board[end_pair.0][end_pair.1] == Square::Empty {
-                board.board[start_pair.0][end_pair.1] = Square::Empty;
-                board.zobrist_key ^= zobrist_hasher.get_val_for_piece(
-                    Piece::pawn(board.to_move.opposite()),
-                    Point(start_pair.0, end_pair.1),
-                );
-            }
+    let moves = generate_moves(board, MoveGenerationMode::AllMoves);
+    match moves.into_iter().find(|mov| mov.to_string() == player_move) {
+        Some(mov) => {
+            board.make_move(mov, zobrist_hasher);
+        }
+        None => {
+            error!("Got illegal move {} in {}", player_move, board.to_fen());
+            panic!("UCI Error: Trying to make a move that is not legal");
         }
-    } else {
-        panic!("UCI Error: Trying to move a piece that does not exist");
-    }
-
-    //deal with castling privileges related to the movement/capture of rooks
-    if player_move.contains("a8") {
-        board.take_away_castling_rights(CastlingType::BlackQueenSide, zobrist_hasher);
-    }
-    if player_move.contains("h8") {
-        board.take_away_castling_rights(CastlingType::BlackKingSide, zobrist_hasher);
-    }
-    if player_move.contains("a1") {
-        board.take_away_castling_rights(CastlingType::WhiteQueenSide, zobrist_hasher);
-    }
-    if player_move.contains("h1") {
-        board.take_away_castling_rights(CastlingType::WhiteKingSide, zobrist_hasher);
-    }
-
-    //move piece
-    board.move_piece(start_pair, end_pair, zobrist_hasher);
-
-    //deal with any pawn promotions
-    if player_move.len() == 5 {
-        let kind = match player_move.chars().nth(4).unwrap() {
-            'q' => Queen,
-            'n' => Knight,
-            'b' => Bishop,
-            'r' => Rook,
-            _ => {
-                error!("Could not recognize piece value, default to queen");
-                Queen
-            }
-        };
-        let promotion_piece = Piece {
-            color: board.to_move,
-            kind,
-        };
-        board.zobrist_key ^= zobrist_hasher.get_val_for_piece(Piece::pawn(board.to_move), end_pair)
-            ^ zobrist_hasher.get_val_for_piece(promotion_piece, end_pair);
-        board.board[end_pair.0][end_pair.1] = promotion_piece.into();
-    }
-
-    // deal with castling, here we also make sure the right king is on the target square to
-    // distinguish between castling and normal moves
-    if player_move == WHITE_KING_SIDE_CASTLE_STRING
-        && board.board[end_pair.0][end_pair.1] == Piece::king(White)
-    {
-        board.move_piece(
-            Point(BOARD_END - 1, BOARD_END - 1),
-            Point(BOARD_END - 1, BOARD_END - 3),
-            zobrist_hasher,
-        );
-    } else if player_move == WHITE_QUEEN_SIDE_CASTLE_STRING
-        && board.board[end_pair.0][end_pair.1] == Piece::king(White)
-    {
-        board.move_piece(
-            Point(BOARD_END - 1, BOARD_START),
-            Point(BOARD_END - 1, BOARD_START + 3),
-            zobrist_hasher,
-        );
-    } else if player_move == BLACK_KING_SIDE_CASTLE_STRING
-        && board.board[end_pair.0][end_pair.1] == Piece::king(Black)
-    {
-        board.move_piece(
-            Point(BOARD_START, BOARD_END - 1),
-            Point(BOARD_START, BOARD_END - 3),
-            zobrist_hasher,
-        );
-    } else if player_move == BLACK_QUEEN_SIDE_CASTLE_STRING
-        && board.board[end_pair.0][end_pair.1] == Piece::king(Black)
-    {
-        board.move_piece(
-            Point(BOARD_START, BOARD_START),
-            Point(BOARD_START, BOARD_START + 3),
-            zobrist_hasher,
-        );
     }
-
-    board.swap_color(zobrist_hasher);
 }
 
-fn send_best_move_to_gui(board: &BoardState) {
-    let best_move = board.last_move.unwrap();
-    if let Some(pawn_promotion) = board.pawn_promotion {
-        send_to_gui(&format!(
-            "bestmove {}{}{}",
-            best_move.0,
-            best_move.1,
-            pawn_promotion.kind.alg()
-        ));
-    } else {
-        send_to_gui(&format!("bestmove {}{}", best_move.0, best_move.1));
+fn send_best_move_to_gui(result: Option<SearchResult>) {
+    match result {
+        Some(SearchResult {
+            best_move,
+            ponder_move: Some(ponder_move),
+            ..
+        }) => send_to_gui(&format!("bestmove {} ponder {}", best_move, ponder_move)),
+        Some(result) => send_to_gui(&format!("bestmove {}", result.best_move)),
+        // there are no legal moves, but the GUI is still owed a reply
+        None => send_to_gui("bestmove 0000"),
     }
 }
 
@@ -342,7 +567,7 @@ mod tests {
     fn can_parse_go_command_no_inc() {
         let buffer = "go wtime 12345 btime 300000 movestogo 40";
         let commands: Vec<&str> = buffer.split(' ').collect();
-        let res = parse_go_command(&commands);
+        let res = parse_go_command(&commands).clock.unwrap();
         assert_eq!(res.winc, 0);
         assert_eq!(res.binc, 0);
         assert_eq!(res.wtime, 12345);
@@ -354,7 +579,7 @@ mod tests {
     fn can_parse_go_command() {
         let buffer = "go wtime 300000 btime 300000 winc 1 binc 2 movestogo 40";
         let commands: Vec<&str> = buffer.split(' ').collect();
-        let res = parse_go_command(&commands);
+        let res = parse_go_command(&commands).clock.unwrap();
         assert_eq!(res.winc, 1);
         assert_eq!(res.binc, 2);
         assert_eq!(res.wtime, 300000);
@@ -366,7 +591,7 @@ mod tests {
     fn can_parse_go_command_no_moves_to_go() {
         let buffer = "go wtime 300000 btime 300000 winc 1 binc 2";
         let commands: Vec<&str> = buffer.split(' ').collect();
-        let res = parse_go_command(&commands);
+        let res = parse_go_command(&commands).clock.unwrap();
         assert_eq!(res.winc, 1);
         assert_eq!(res.binc, 2);
         assert_eq!(res.wtime, 300000);
@@ -374,6 +599,249 @@ mod tests {
         assert_eq!(res.movestogo, None);
     }
 
+    #[test]
+    fn can_parse_go_command_limits() {
+        let commands: Vec<&str> = "go depth 7 nodes 50000 movetime 2500 mate 3"
+            .split(' ')
+            .collect();
+        let res = parse_go_command(&commands);
+        assert_eq!(res.depth, 7);
+        assert_eq!(res.nodes, 50000);
+        assert_eq!(res.movetime, Some(2500));
+        assert_eq!(res.mate, Some(3));
+        assert_eq!(res.clock, None);
+        assert!(!res.infinite);
+
+        let commands: Vec<&str> = "go infinite".split(' ').collect();
+        let res = parse_go_command(&commands);
+        assert!(res.infinite);
+        assert_eq!(
+            res,
+            SearchLimits {
+                infinite: true,
+                ..SearchLimits::default()
+            }
+        );
+
+        let commands: Vec<&str> = "go ponder wtime 1000 btime 2000".split(' ').collect();
+        let res = parse_go_command(&commands);
+        assert!(res.ponder);
+        assert_eq!(res.clock.unwrap().btime, 2000);
+
+        let commands: Vec<&str> = "go depth 250".split(' ').collect();
+        assert_eq!(parse_go_command(&commands).depth, MAX_DEPTH - 1);
+    }
+
+    #[test]
+    fn invalid_go_values_ignored() {
+        let commands: Vec<&str> = "go wtime abc btime 1000 depth x nodes mate 0 movetime"
+            .split(' ')
+            .collect();
+        let res = parse_go_command(&commands);
+        assert_eq!(res.clock.unwrap().wtime, 0);
+        assert_eq!(res.clock.unwrap().btime, 1000);
+        assert_eq!(res.depth, SearchLimits::default().depth);
+        assert_eq!(res.nodes, u64::MAX);
+        assert_eq!(res.mate, None);
+        assert_eq!(res.movetime, None);
+    }
+
+    #[test]
+    fn can_parse_search_moves() {
+        let board = BoardState::from_fen(DEFAULT_FEN_STRING).unwrap();
+        let commands: Vec<&str> = "go searchmoves e2e4 g1f3 depth 5".split(' ').collect();
+        let search_moves: Vec<String> = parse_search_moves(&commands, &board)
+            .iter()
+            .map(|mov| mov.to_string())
+            .collect();
+        assert_eq!(search_moves, vec!["e2e4", "g1f3"]);
+        assert_eq!(parse_go_command(&commands).depth, 5);
+
+        // illegal moves are left out
+        let commands: Vec<&str> = "go wtime 100 searchmoves e2e5 d2d4 xyz"
+            .split(' ')
+            .collect();
+        let search_moves = parse_search_moves(&commands, &board);
+        assert_eq!(search_moves.len(), 1);
+        assert_eq!(search_moves[0].to_string(), "d2d4");
+
+        let commands: Vec<&str> = "go searchmoves a1a5 infinite".split(' ').collect();
+        assert!(parse_search_moves(&commands, &board).is_empty());
+        let commands: Vec<&str> = "go infinite".split(' ').collect();
+        assert!(parse_search_moves(&commands, &board).is_empty());
+    }
+
+    fn search_request(fen: &str, limits: SearchLimits) -> SearchRequest {
+        let board = BoardState::from_fen(fen).unwrap();
+        let mut draw_table = DrawTable::new();
+        draw_table.add_board_to_draw_table(&board);
+        SearchRequest {
+            board,
+            draw_table,
+            start: Instant::now(),
+            limits,
+            threads: 1,
+            params: SearchParams::default(),
+            transposition_table: Arc::new(TranspositionTable::new(1)),
+        }
+    }
+
+    #[test]
+    fn infinite_search_runs_until_stopped() {
+        let mut search_controller = SearchController::new();
+        // the search finishes early, but the bestmove still has to wait for stop
+        let limits = SearchLimits {
+            depth: 2,
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        search_controller.go(search_request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!search_controller.handle.as_ref().unwrap().is_finished());
+        let best_move = search_controller.stop();
+        assert_eq!(
+            best_move.map(|mov| mov.to_string()),
+            Some("a1a8".to_string())
+        );
+        assert!(search_controller.handle.is_none());
+    }
+
+    #[test]
+    fn ponder_search_waits_for_ponderhit() {
+        let mut search_controller = SearchController::new();
+        let limits = SearchLimits {
+            depth: 2,
+            ponder: true,
+            ..SearchLimits::default()
+        };
+        search_controller.go(search_request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits));
+        thread::sleep(Duration::from_millis(50));
+        assert!(!search_controller.handle.as_ref().unwrap().is_finished());
+
+        search_controller.ponderhit();
+        assert_eq!(
+            search_controller.wait().map(|mov| mov.to_string()),
+            Some("a1a8".to_string())
+        );
+    }
+
+    #[test]
+    fn ponderhit_switches_to_time_limit() {
+        let mut search_controller = SearchController::new();
+        let limits = SearchLimits {
+            ponder: true,
+            ..SearchLimits::time(100)
+        };
+        search_controller.go(search_request(DEFAULT_FEN_STRING, limits));
+        thread::sleep(Duration::from_millis(200));
+        // still searching well past the time to move
+        assert!(!search_controller.handle.as_ref().unwrap().is_finished());
+
+        let ponderhit = Instant::now();
+        search_controller.ponderhit();
+        assert!(search_controller.wait().is_some());
+        assert!(ponderhit.elapsed().as_millis() < 5_000);
+    }
+
+    #[test]
+    fn ponder_miss_stopped() {
+        let mut search_controller = SearchController::new();
+        let limits = SearchLimits {
+            ponder: true,
+            ..SearchLimits::time(100)
+        };
+        search_controller.go(search_request(DEFAULT_FEN_STRING, limits));
+        thread::sleep(Duration::from_millis(20));
+        // the GUI stops the search, and must still get a bestmove back
+        assert!(search_controller.stop().is_some());
+        // a late ponderhit is ignored
+        search_controller.ponderhit();
+        assert!(search_controller.stop().is_none());
+    }
+
+    #[test]
+    fn search_stopped_early() {
+        let mut search_controller = SearchController::new();
+        let start = Instant::now();
+        search_controller.go(search_request(
+            DEFAULT_FEN_STRING,
+            SearchLimits::time(60_000),
+        ));
+        thread::sleep(Duration::from_millis(20));
+        assert!(search_controller.stop().is_some());
+        assert!(start.elapsed().as_millis() < 5_000);
+        // nothing is running so there is no bestmove to send
+        assert!(search_controller.stop().is_none());
+    }
+
+    #[test]
+    fn new_search_replaces_running_search() {
+        let mut search_controller = SearchController::new();
+        let limits = SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        search_controller.go(search_request(DEFAULT_FEN_STRING, limits));
+        let limits = SearchLimits {
+            depth: 1,
+            ..SearchLimits::default()
+        };
+        search_controller.go(search_request("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", limits));
+        assert_eq!(
+            search_controller.finish().map(|mov| mov.to_string()),
+            Some("a1a8".to_string())
+        );
+    }
+
+    #[test]
+    fn bestmove_sent_without_legal_moves() {
+        let mut search_controller = SearchController::new();
+        search_controller.go(search_request(
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            SearchLimits::time(100),
+        ));
+        assert!(search_controller.finish().is_none());
+    }
+
+    #[test]
+    fn can_parse_option_value() {
+        let buffer = "setoption name Hash value 128";
+        let commands: Vec<&str> = buffer.split(' ').collect();
+        assert_eq!(parse_option_value(&commands), Some("128"));
+
+        let buffer = "setoption name Hash value";
+        let commands: Vec<&str> = buffer.split(' ').collect();
+        assert_eq!(parse_option_value(&commands), None);
+    }
+
+    #[test]
+    fn can_parse_option_name() {
+        let commands: Vec<&str> = "setoption name LmrBase value 80".split(' ').collect();
+        assert_eq!(parse_option_name(&commands), Some("LmrBase".to_string()));
+
+        let commands: Vec<&str> = "setoption name Clear Hash".split(' ').collect();
+        assert_eq!(parse_option_name(&commands), Some("Clear Hash".to_string()));
+
+        let commands: Vec<&str> = "setoption name value 3".split(' ').collect();
+        assert_eq!(parse_option_name(&commands), None);
+    }
+
+    #[test]
+    fn move_counters_tracked_through_position_command() {
+        let buffer = "position startpos moves g1f3 g8f6 f3g1 f6g8 e2e4";
+        let commands: Vec<&str> = buffer.split(' ').collect();
+        let zobrist_hasher = ZobristHasher::create_zobrist_hasher();
+        let mut draw_table = DrawTable::new();
+        let mut board = play_out_position(&commands, &zobrist_hasher, &mut draw_table);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3"
+        );
+        make_move(&mut board, "b8c6", &zobrist_hasher);
+        assert_eq!(board.half_move_clock, 1);
+        assert_eq!(board.full_move_number, 4);
+    }
+
     #[test]
     fn en_passant_capture_parsed_correctly_black() {
         let mut board = BoardState::from_fen("8/1k6/8/8/7p/8/1K4P1/8 w - - 0 1").unwrap();